@@ -1,12 +1,93 @@
+use std::fmt::Write;
+
 use parley::layout::PositionedLayoutItem;
 
-use crate::Document;
+use crate::{Document, Node, NodeData};
+
+/// Node counts and estimated memory use, as returned by [`Document::memory_report`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub element_nodes: usize,
+    pub anonymous_block_nodes: usize,
+    pub text_nodes: usize,
+    pub comment_nodes: usize,
+    /// The total number of attributes across all elements
+    pub attributes: usize,
+    /// Bytes allocated for the content of text nodes
+    pub text_bytes: usize,
+    /// Bytes allocated for attribute values (attribute names are interned and not counted)
+    pub attribute_bytes: usize,
+    /// Bytes allocated for the node slab itself, including vacant slots
+    pub node_bytes: usize,
+}
 
 impl Document {
     pub fn print_taffy_tree(&self) {
         taffy::print_tree(self, taffy::NodeId::from(0usize));
     }
 
+    /// Render the DOM tree as a Graphviz DOT digraph
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph dom {\n");
+
+        let root = self.root_node();
+        for node in std::iter::once(root).chain(root.descendants()) {
+            let label = match &node.raw_dom_data {
+                NodeData::Document => String::from("#document"),
+                NodeData::Element(data) => format!("<{}>", data.name.local),
+                NodeData::AnonymousBlock(_) => String::from("AnonymousBlock"),
+                NodeData::Text(data) => data.content.trim().chars().take(20).collect(),
+                NodeData::Comment => String::from("#comment"),
+            };
+            let style = match &node.raw_dom_data {
+                NodeData::Comment | NodeData::AnonymousBlock(_) => ", style=dashed",
+                _ => "",
+            };
+            writeln!(
+                out,
+                "  n{id} [label=\"{id} {}\"{style}];",
+                label.replace('\\', "\\\\").replace('"', "\\\""),
+                id = node.id
+            )
+            .unwrap();
+
+            for child_id in &node.children {
+                writeln!(out, "  n{} -> n{};", node.id, child_id).unwrap();
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Count the nodes (including detached ones) by kind and estimate their memory use
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport {
+            node_bytes: self.nodes.capacity() * std::mem::size_of::<Node>(),
+            ..Default::default()
+        };
+
+        for (_, node) in self.nodes.iter() {
+            match &node.raw_dom_data {
+                NodeData::Document => {}
+                NodeData::Element(_) => report.element_nodes += 1,
+                NodeData::AnonymousBlock(_) => report.anonymous_block_nodes += 1,
+                NodeData::Text(data) => {
+                    report.text_nodes += 1;
+                    report.text_bytes += data.content.capacity();
+                }
+                NodeData::Comment => report.comment_nodes += 1,
+            }
+
+            for attr in node.raw_dom_data.attrs().into_iter().flatten() {
+                report.attributes += 1;
+                report.attribute_bytes += attr.value.capacity();
+            }
+        }
+
+        report
+    }
+
     pub fn debug_log_node(&self, node_id: usize) {
         let node = &self.nodes[node_id];
 
@@ -137,3 +218,105 @@ impl Document {
         // taffy::print_tree(&self.dom, node_id.into());
     }
 }
+
+/// Prints the DOM tree: one node per line, indented by depth
+impl std::fmt::Debug for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Use an explicit stack so that very deep trees can't overflow the call stack
+        let mut stack = vec![(self.root_node().id, 0)];
+        while let Some((node_id, depth)) = stack.pop() {
+            let node = &self.nodes[node_id];
+            write!(f, "{}{} ", "  ".repeat(depth), node.id)?;
+            match &node.raw_dom_data {
+                NodeData::Document => write!(f, "#document")?,
+                NodeData::Element(data) => {
+                    write!(f, "<{}", data.name.local)?;
+                    for attr in data.attrs() {
+                        write!(f, " {}={:?}", attr.name.local, attr.value)?;
+                    }
+                    write!(f, ">")?;
+                }
+                NodeData::AnonymousBlock(_) => write!(f, "AnonymousBlock")?,
+                NodeData::Text(data) => write!(f, "#text {:?}", data.content)?,
+                NodeData::Comment => write!(f, "#comment")?,
+            }
+            writeln!(f)?;
+
+            // Push children in reverse so that the first child is printed first
+            stack.extend(node.children.iter().rev().map(|id| (*id, depth + 1)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::tests::{deep_chain, element, element_with_attrs, test_document, text};
+    use std::fmt::Write;
+
+    #[test]
+    fn to_dot_only_escapes_quotes_and_backslashes() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let p = element(&mut doc, html, "p");
+        let t = text(&mut doc, p, r#"café "x" \"#);
+
+        let dot = doc.to_dot();
+        assert!(dot.starts_with("digraph dom {\n"));
+        assert!(dot.contains(&format!("n{html} -> n{p};")));
+        assert!(dot.contains(&format!(r#"n{t} [label="{t} café \"x\" \\"];"#)));
+    }
+
+    #[test]
+    fn debug_prints_an_indented_tree() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let p = element_with_attrs(&mut doc, html, "p", &[("id", "a")]);
+        let hi = text(&mut doc, p, "hi");
+
+        let expected = format!(
+            "0 #document\n  {html} <html>\n    {p} <p id=\"a\">\n      {hi} #text \"hi\"\n"
+        );
+        assert_eq!(format!("{doc:?}"), expected);
+    }
+
+    #[test]
+    fn debug_does_not_recurse_on_deep_trees() {
+        struct LineCounter(usize);
+        impl std::fmt::Write for LineCounter {
+            fn write_str(&mut self, s: &str) -> std::fmt::Result {
+                self.0 += s.matches('\n').count();
+                Ok(())
+            }
+        }
+
+        let mut doc = test_document();
+        deep_chain(&mut doc, 0, 10_000);
+
+        let mut counter = LineCounter(0);
+        write!(counter, "{doc:?}").unwrap();
+        assert_eq!(counter.0, 10_001);
+    }
+
+    #[test]
+    fn memory_report_counts_nodes_by_kind() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        element_with_attrs(&mut doc, html, "div", &[("id", "abc"), ("class", "de")]);
+        text(&mut doc, html, "hello");
+        let comment = doc.create_node(crate::NodeData::Comment);
+        doc.append_children(html, &[comment]);
+        // Detached nodes are counted too
+        doc.create_text_node("detached");
+
+        let report = doc.memory_report();
+        assert_eq!(report.element_nodes, 2);
+        assert_eq!(report.text_nodes, 2);
+        assert_eq!(report.comment_nodes, 1);
+        assert_eq!(report.anonymous_block_nodes, 0);
+        assert_eq!(report.attributes, 2);
+        assert!(report.text_bytes >= "hellodetached".len());
+        assert!(report.attribute_bytes >= "abcde".len());
+        assert!(report.node_bytes >= 6 * std::mem::size_of::<crate::Node>());
+    }
+}
@@ -1,19 +1,20 @@
 use crate::events::{apply_keypress_event, EventData, HitResult, RendererEvent};
 use crate::layout::construct::collect_layout_children;
-use crate::node::{ImageData, NodeSpecificData, Status, TextBrush};
+use crate::node::{Attribute, ImageData, NodeSpecificData, Status, TextBrush};
 use crate::stylo_to_cursor_icon::stylo_to_cursor_icon;
 use crate::util::ImageType;
-use crate::{ElementNodeData, Node, NodeData, TextNodeData};
+use crate::{ElementNodeData, Node, NodeData, QualName, TextNodeData};
 use app_units::Au;
 use blitz_traits::net::{DummyNetProvider, SharedProvider};
 use blitz_traits::{ColorScheme, Viewport};
 use cursor_icon::CursorIcon;
-use markup5ever::local_name;
+use markup5ever::{local_name, LocalName};
 use parley::FontContext;
 use peniko::kurbo;
 use string_cache::Atom;
 use style::attr::{AttrIdentifier, AttrValue};
 use style::data::{ElementData, ElementStyles};
+use style::invalidation::element::restyle_hints::RestyleHint;
 use style::properties::style_structs::Font;
 use style::properties::ComputedValues;
 use style::values::computed::Overflow;
@@ -24,7 +25,9 @@ use crate::net::{Resource, StylesheetLoader};
 use selectors::{matching::QuirksMode, Element};
 use slab::Slab;
 use std::any::Any;
+use std::borrow::Cow;
 use std::collections::{BTreeMap, Bound, HashMap, HashSet, VecDeque};
+use std::ops::ControlFlow;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use style::media_queries::MediaType;
@@ -89,6 +92,50 @@ pub trait DocumentLike: AsRef<Document> + AsMut<Document> + Into<Document> + 'st
     }
 }
 
+/// Why a node can't be inserted as a child of another node (see [`Document::validate_insert`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertError {
+    /// There is no node with this id
+    MissingNode(usize),
+    /// The parent is a text node or comment, which can't have children
+    ParentCannotHaveChildren,
+    /// The node is the parent itself or one of its ancestors
+    Cycle,
+    /// The node is still attached to a parent and must be removed from it first
+    AlreadyAttached { parent_id: usize },
+}
+
+/// The error returned by [`Document::set_text`] when the node isn't a text node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotATextNode;
+
+/// Callbacks for [`Document::walk`]
+pub trait TreeVisitor {
+    /// Called before any of the node's descendants are visited
+    fn enter(&mut self, _node: &Node) {}
+    /// Called after all of the node's descendants have been visited
+    fn leave(&mut self, _node: &Node) {}
+}
+
+/// An inconsistency in the tree structure, as returned by [`Document::check_invariants`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// The root node has a parent
+    RootHasParent,
+    /// A node's child list references a node which doesn't exist
+    MissingChild { parent_id: usize, child_id: usize },
+    /// A node's parent doesn't exist
+    MissingParent { node_id: usize, parent_id: usize },
+    /// A node is in `parent_id`'s child list, but its `parent` field doesn't point back
+    ParentMismatch { parent_id: usize, child_id: usize },
+    /// A node's `parent` field points to `parent_id`, but it isn't in its child list
+    NotInParentChildren { node_id: usize, parent_id: usize },
+    /// A node appears more than once in its parent's child list
+    DuplicateChild { parent_id: usize, child_id: usize },
+    /// Following `parent` links from this node never reaches a node without a parent
+    Cycle { node_id: usize },
+}
+
 pub struct Document {
     id: usize,
 
@@ -378,6 +425,11 @@ impl Document {
         self.nodes.get(node_id)
     }
 
+    /// The depth of a node (see [`Node::depth`]), or `None` if it doesn't exist
+    pub fn node_depth(&self, node_id: usize) -> Option<usize> {
+        self.nodes.get(node_id).map(Node::depth)
+    }
+
     pub fn get_node_mut(&mut self, node_id: usize) -> Option<&mut Node> {
         self.nodes.get_mut(node_id)
     }
@@ -481,24 +533,84 @@ impl Document {
         self.create_node(data)
     }
 
+    /// Create a detached element, returning its id. Tag-specific setup (e.g. loading `<link>`
+    /// stylesheets and images, or `<input>` state) is left to the caller.
+    pub fn create_element(&mut self, name: QualName, attrs: Vec<Attribute>) -> usize {
+        let mut data = ElementNodeData::new(name, attrs);
+        data.flush_style_attribute(&self.guard);
+        let id = self.create_node(NodeData::Element(data));
+        let node = &self.nodes[id];
+
+        // Initialise style data
+        *node.stylo_element_data.borrow_mut() = Some(Default::default());
+
+        // If the node has an "id" attribute, store it in the ID map.
+        if let Some(id_attr) = node.attr(local_name!("id")).map(str::to_string) {
+            self.nodes_to_id.insert(id_attr, id);
+        }
+
+        id
+    }
+
+    /// Clone a node along with its entire subtree, returning the id of the new node
     pub fn deep_clone_node(&mut self, node_id: usize) -> usize {
-        // Load existing node
+        self.clone_subtree_with(node_id, |doc, old_id| {
+            let new_id = doc.shallow_clone_node(old_id);
+            (new_id, doc.nodes[old_id].children.clone())
+        })
+    }
+
+    /// Clone a subtree from another document, returning the id of the new detached root.
+    /// Stylesheets of `<style>` and `<link>` elements in the subtree are not registered.
+    pub fn clone_subtree_from(&mut self, source: &Document, source_root_id: usize) -> usize {
+        self.clone_subtree_with(source_root_id, |doc, source_id| {
+            let source_node = &source.nodes[source_id];
+            let mut data = source_node.raw_dom_data.clone();
+            // The parsed style attribute is tied to the source document's lock
+            if let Some(element) = data.downcast_element_mut() {
+                element.flush_style_attribute(&doc.guard);
+            }
+
+            let new_id = doc.create_node(data);
+            doc.nodes[new_id].listeners = source_node.listeners.clone();
+            (new_id, source_node.children.clone())
+        })
+    }
+
+    /// Clone a subtree without recursing. `clone_node` clones a single source node, returning
+    /// the new node's id and the source node's children.
+    fn clone_subtree_with(
+        &mut self,
+        source_root_id: usize,
+        mut clone_node: impl FnMut(&mut Document, usize) -> (usize, Vec<usize>),
+    ) -> usize {
+        let (new_root_id, children) = clone_node(self, source_root_id);
+
+        // Pairs of (source node's children, cloned node) where the children still need cloning
+        let mut stack = vec![(children, new_root_id)];
+        while let Some((children, new_id)) = stack.pop() {
+            let mut new_children = Vec::with_capacity(children.len());
+            for child_id in children {
+                let (new_child_id, grandchildren) = clone_node(self, child_id);
+                self.nodes[new_child_id].parent = Some(new_id);
+                new_children.push(new_child_id);
+                stack.push((grandchildren, new_child_id));
+            }
+            self.nodes[new_id].children = new_children;
+        }
+
+        new_root_id
+    }
+
+    /// Clone a node's data (but not its children), returning the id of the new node
+    fn shallow_clone_node(&mut self, node_id: usize) -> usize {
         let node = &self.nodes[node_id];
         let data = node.raw_dom_data.clone();
-        let children = node.children.clone();
+        let listeners = node.listeners.clone();
 
-        // Create new node
+        // Create new node (this also marks it as changed so that it gets styled)
         let new_node_id = self.create_node(data);
-
-        // Recursively clone children
-        let new_children: Vec<usize> = children
-            .into_iter()
-            .map(|child_id| self.deep_clone_node(child_id))
-            .collect();
-        for &child_id in &new_children {
-            self.nodes[child_id].parent = Some(new_node_id);
-        }
-        self.nodes[new_node_id].children = new_children;
+        self.nodes[new_node_id].listeners = listeners;
 
         new_node_id
     }
@@ -511,6 +623,7 @@ impl Document {
         let node = &self.nodes[node_id];
 
         let parent_id = node.parent.unwrap();
+        let inserted_node_ids = self.without_cyclic_insertions(parent_id, inserted_node_ids);
         let parent = &mut self.nodes[parent_id];
         let node_child_idx = parent
             .children
@@ -542,16 +655,288 @@ impl Document {
     pub fn append(&mut self, node_id: usize, appended_node_ids: &[usize]) {
         let node = &self.nodes[node_id];
         let parent_id = node.parent.unwrap();
+        let appended_node_ids = self.without_cyclic_insertions(parent_id, appended_node_ids);
         self.nodes[parent_id]
             .children
-            .extend_from_slice(appended_node_ids);
+            .extend_from_slice(&appended_node_ids);
 
         // Update parent values
-        for &child_id in appended_node_ids {
+        for &child_id in appended_node_ids.iter() {
             self.nodes[child_id].parent = Some(parent_id);
         }
     }
 
+    /// Append nodes to the end of `parent_id`'s child list (see [`Document::insert_children_at`])
+    pub fn append_children(&mut self, parent_id: usize, child_ids: &[usize]) {
+        let index = self.nodes[parent_id].children.len();
+        self.insert_children_at(parent_id, index, child_ids);
+    }
+
+    /// Insert nodes into `parent_id`'s child list at `index`, detaching them from any old parent.
+    /// `index` is applied after detaching, and is clamped to the length of the child list.
+    /// Duplicate ids are only inserted once, at the position of their first occurrence.
+    pub fn insert_children_at(&mut self, parent_id: usize, index: usize, child_ids: &[usize]) {
+        let mut child_ids = self.without_cyclic_insertions(parent_id, child_ids);
+        if child_ids.is_empty() {
+            return;
+        }
+        // A duplicate would end up in the child list twice, as the detaching below can't see it
+        if child_ids.len() > 1 {
+            let mut seen = HashSet::with_capacity(child_ids.len());
+            if !child_ids.iter().all(|&child_id| seen.insert(child_id)) {
+                seen.clear();
+                child_ids.to_mut().retain(|&child_id| seen.insert(child_id));
+            }
+        }
+        // Attached children are detached below, so that isn't an error here
+        debug_assert!(
+            child_ids.iter().all(|&child_id| matches!(
+                self.validate_insert(parent_id, child_id),
+                Ok(()) | Err(InsertError::AlreadyAttached { .. })
+            )),
+            "invalid insertion into node {parent_id}"
+        );
+
+        // Mark the parent as changed (once), and each child (and its old parent) as changed.
+        self.changed.insert(parent_id);
+        for &child_id in child_ids.iter() {
+            if let Some(old_parent_id) = self.nodes[child_id].parent {
+                self.changed.insert(old_parent_id);
+                self.remove_node(child_id);
+            }
+            self.nodes[child_id].parent = Some(parent_id);
+            self.changed.insert(child_id);
+        }
+
+        let children = &mut self.nodes[parent_id].children;
+        let index = index.min(children.len());
+        children.splice(index..index, child_ids.iter().copied());
+    }
+
+    /// Replace all of `parent_id`'s children. The old children are detached but not dropped.
+    pub fn replace_children(&mut self, parent_id: usize, child_ids: &[usize]) {
+        let old_children = std::mem::take(&mut self.nodes[parent_id].children);
+        for child_id in old_children {
+            self.nodes[child_id].parent = None;
+        }
+
+        self.changed.insert(parent_id);
+        self.insert_children_at(parent_id, 0, child_ids);
+    }
+
+    /// Move a node (and its subtree) to be the `index`-th child of `new_parent_id`.
+    /// Returns `false` if that would make the node a descendant of itself.
+    pub fn move_node(&mut self, node_id: usize, new_parent_id: usize, index: usize) -> bool {
+        if self.is_inclusive_ancestor(node_id, new_parent_id) {
+            return false;
+        }
+
+        // Mark both the old and the new parent as changed
+        if let Some(old_parent_id) = self.nodes[node_id].parent {
+            self.changed.insert(old_parent_id);
+        }
+        self.changed.insert(new_parent_id);
+        self.changed.insert(node_id);
+
+        self.remove_node(node_id);
+
+        let new_parent = &mut self.nodes[new_parent_id];
+        let index = index.min(new_parent.children.len());
+        new_parent.children.insert(index, node_id);
+        self.nodes[node_id].parent = Some(new_parent_id);
+
+        true
+    }
+
+    /// Swap the positions of two nodes, which may have different parents.
+    /// Returns `false` if either node has no parent or one is an ancestor of the other.
+    pub fn swap_nodes(&mut self, a: usize, b: usize) -> bool {
+        if self.is_inclusive_ancestor(a, b) || self.is_inclusive_ancestor(b, a) {
+            return false;
+        }
+        let (Some(parent_a), Some(parent_b)) = (self.nodes[a].parent, self.nodes[b].parent) else {
+            return false;
+        };
+
+        let idx_a = self.nodes[a].child_index().unwrap();
+        let idx_b = self.nodes[b].child_index().unwrap();
+        self.nodes[parent_a].children[idx_a] = b;
+        self.nodes[parent_b].children[idx_b] = a;
+        self.nodes[a].parent = Some(parent_b);
+        self.nodes[b].parent = Some(parent_a);
+
+        self.changed.extend([parent_a, parent_b, a, b]);
+
+        true
+    }
+
+    /// Swap a node with its previous sibling, returning `false` if it has none
+    pub fn move_node_up(&mut self, node_id: usize) -> bool {
+        let prev_id = self.nodes[node_id]
+            .preceding_siblings()
+            .next()
+            .map(|node| node.id);
+        prev_id.is_some_and(|prev_id| self.swap_nodes(node_id, prev_id))
+    }
+
+    /// Swap a node with its next sibling, returning `false` if it has none
+    pub fn move_node_down(&mut self, node_id: usize) -> bool {
+        let next_id = self.nodes[node_id]
+            .following_siblings()
+            .next()
+            .map(|node| node.id);
+        next_id.is_some_and(|next_id| self.swap_nodes(node_id, next_id))
+    }
+
+    /// Check whether `node_id` can be inserted as a child of `parent_id`
+    pub fn validate_insert(&self, parent_id: usize, node_id: usize) -> Result<(), InsertError> {
+        let parent = self
+            .nodes
+            .get(parent_id)
+            .ok_or(InsertError::MissingNode(parent_id))?;
+        let node = self
+            .nodes
+            .get(node_id)
+            .ok_or(InsertError::MissingNode(node_id))?;
+
+        if matches!(parent.raw_dom_data, NodeData::Text(_) | NodeData::Comment) {
+            return Err(InsertError::ParentCannotHaveChildren);
+        }
+        if self.is_inclusive_ancestor(node_id, parent_id) {
+            return Err(InsertError::Cycle);
+        }
+        if let Some(parent_id) = node.parent {
+            return Err(InsertError::AlreadyAttached { parent_id });
+        }
+
+        Ok(())
+    }
+
+    /// Check that parent and child links agree, there are no duplicate children and no cycles.
+    /// Anonymous blocks and pseudo-elements (which are created by layout) are skipped.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        if self.root_node().parent.is_some() {
+            return Err(InvariantViolation::RootHasParent);
+        }
+
+        for (node_id, node) in self.nodes.iter() {
+            // Layout links these into the tree one way only (e.g. via `Node::before`)
+            if node.raw_dom_data.is_anonymous_block() {
+                continue;
+            }
+
+            let mut seen_children = HashSet::with_capacity(node.children.len());
+            for &child_id in &node.children {
+                let Some(child) = self.nodes.get(child_id) else {
+                    return Err(InvariantViolation::MissingChild {
+                        parent_id: node_id,
+                        child_id,
+                    });
+                };
+                if child.parent != Some(node_id) {
+                    return Err(InvariantViolation::ParentMismatch {
+                        parent_id: node_id,
+                        child_id,
+                    });
+                }
+                if !seen_children.insert(child_id) {
+                    return Err(InvariantViolation::DuplicateChild {
+                        parent_id: node_id,
+                        child_id,
+                    });
+                }
+            }
+
+            if let Some(parent_id) = node.parent {
+                let Some(parent) = self.nodes.get(parent_id) else {
+                    return Err(InvariantViolation::MissingParent { node_id, parent_id });
+                };
+                if !parent.children.contains(&node_id) {
+                    return Err(InvariantViolation::NotInParentChildren { node_id, parent_id });
+                }
+            }
+
+            // A chain of parent links can't be longer than the number of nodes unless it loops
+            let mut steps = 0;
+            let mut maybe_id = node.parent;
+            while let Some(id) = maybe_id {
+                steps += 1;
+                if steps > self.nodes.len() {
+                    return Err(InvariantViolation::Cycle { node_id });
+                }
+                // Missing parents are reported when the node with the dangling link is checked
+                maybe_id = self.nodes.get(id).and_then(|node| node.parent);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Filter out nodes which are `parent_id` itself or one of its ancestors
+    fn without_cyclic_insertions<'a>(
+        &self,
+        parent_id: usize,
+        node_ids: &'a [usize],
+    ) -> Cow<'a, [usize]> {
+        // This is on the hot path for DOM mutations, so don't allocate unless there is a cycle
+        let creates_cycle = |id: usize| self.is_inclusive_ancestor(id, parent_id);
+        if !node_ids.iter().any(|&id| creates_cycle(id)) {
+            return Cow::Borrowed(node_ids);
+        }
+
+        let node_ids = node_ids
+            .iter()
+            .copied()
+            .filter(|&id| {
+                debug_assert!(
+                    !creates_cycle(id),
+                    "Inserting node {id} as a child of node {parent_id} would create a cycle"
+                );
+                !creates_cycle(id)
+            })
+            .collect();
+        Cow::Owned(node_ids)
+    }
+
+    /// Whether `node_id` is a (strict) descendant of `ancestor_id`
+    pub fn is_descendant(&self, node_id: usize, ancestor_id: usize) -> bool {
+        self.nodes
+            .get(node_id)
+            .is_some_and(|node| node.is_descendant_of(ancestor_id))
+    }
+
+    /// Returns the deepest node which is an inclusive ancestor of both `a` and `b`.
+    /// Returns `None` if either node doesn't exist or they are in disconnected trees.
+    pub fn lowest_common_ancestor(&self, a: usize, b: usize) -> Option<usize> {
+        let mut a = self.nodes.get(a)?;
+        let mut b = self.nodes.get(b)?;
+
+        // Walk the deeper node up until both nodes are at the same depth
+        let mut depth_a = a.depth();
+        let mut depth_b = b.depth();
+        while depth_a > depth_b {
+            a = &self.nodes[a.parent?];
+            depth_a -= 1;
+        }
+        while depth_b > depth_a {
+            b = &self.nodes[b.parent?];
+            depth_b -= 1;
+        }
+
+        // Then walk both up together until they meet
+        while a.id != b.id {
+            a = &self.nodes[a.parent?];
+            b = &self.nodes[b.parent?];
+        }
+
+        Some(a.id)
+    }
+
+    /// Whether `ancestor_id` is `node_id` itself or one of its ancestors
+    fn is_inclusive_ancestor(&self, ancestor_id: usize, node_id: usize) -> bool {
+        ancestor_id == node_id || self.is_descendant(node_id, ancestor_id)
+    }
+
     /// Remove the node from it's parent but don't drop it
     pub fn remove_node(&mut self, node_id: usize) {
         let node = &mut self.nodes[node_id];
@@ -585,6 +970,141 @@ impl Document {
         node
     }
 
+    /// Merge adjacent text nodes and remove empty ones in a subtree, like the DOM's `normalize()`.
+    /// The first text node of each run keeps its id. Affected `<style>` elements are re-parsed.
+    pub fn normalize(&mut self, node_id: usize) {
+        let root = &self.nodes[node_id];
+        let parent_ids: Vec<usize> = std::iter::once(root)
+            .chain(root.descendants())
+            .filter(|node| !node.children.is_empty())
+            .map(|node| node.id)
+            .collect();
+
+        let mut to_remove = Vec::new();
+        let mut style_ids = Vec::new();
+        for parent_id in parent_ids {
+            let removed_count = to_remove.len();
+            let mut merge_into: Option<usize> = None;
+            for child_id in self.nodes[parent_id].children.clone() {
+                let Some(text) = self.nodes[child_id].raw_dom_data.downcast_text_mut() else {
+                    merge_into = None;
+                    continue;
+                };
+                if text.content.is_empty() {
+                    to_remove.push(child_id);
+                    continue;
+                }
+
+                match merge_into {
+                    Some(target_id) => {
+                        let content = std::mem::take(&mut text.content);
+                        let target = self.nodes[target_id].raw_dom_data.downcast_text_mut();
+                        target.unwrap().content.push_str(&content);
+                        self.changed.insert(target_id);
+                        to_remove.push(child_id);
+                    }
+                    None => merge_into = Some(child_id),
+                }
+            }
+
+            let parent = &self.nodes[parent_id].raw_dom_data;
+            if to_remove.len() > removed_count
+                && parent.is_element_with_tag_name(&local_name!("style"))
+            {
+                style_ids.push(parent_id);
+            }
+        }
+
+        self.remove_and_drop_nodes(&to_remove);
+        for style_id in style_ids {
+            self.upsert_stylesheet_for_node(style_id);
+        }
+    }
+
+    /// Remove and drop several nodes (and their subtrees) at once
+    pub fn remove_and_drop_nodes(&mut self, node_ids: &[usize]) {
+        let to_remove: HashSet<usize> = node_ids
+            .iter()
+            .copied()
+            .filter(|id| self.nodes.contains(*id))
+            .collect();
+
+        // Only remove the topmost nodes: their descendants will be dropped along with them
+        let subtree_roots: Vec<usize> = to_remove
+            .iter()
+            .copied()
+            .filter(|&id| {
+                // Walk the parent links directly: `Node::ancestors` skips parentless roots
+                let mut maybe_id = self.nodes[id].parent;
+                while let Some(ancestor_id) = maybe_id {
+                    if to_remove.contains(&ancestor_id) {
+                        return false;
+                    }
+                    maybe_id = self.nodes[ancestor_id].parent;
+                }
+                true
+            })
+            .collect();
+
+        let mut parents = HashSet::new();
+        for node_id in subtree_roots {
+            if let Some(parent_id) = self.nodes[node_id].parent {
+                parents.insert(parent_id);
+            }
+            self.remove_and_drop_node(node_id);
+        }
+
+        self.changed.extend(parents);
+    }
+
+    /// Remove and drop a node, moving its children into its place in its parent.
+    /// Returns `false` if the node has no parent.
+    pub fn unwrap_node(&mut self, node_id: usize) -> bool {
+        let Some(parent_id) = self.nodes[node_id].parent else {
+            return false;
+        };
+        let node_child_idx = self.nodes[node_id].child_index().unwrap();
+        let children = std::mem::take(&mut self.nodes[node_id].children);
+
+        // Mark the parent and the promoted children as changed
+        self.changed.insert(parent_id);
+        for &child_id in &children {
+            self.nodes[child_id].parent = Some(parent_id);
+            self.changed.insert(child_id);
+        }
+
+        self.nodes[parent_id]
+            .children
+            .splice(node_child_idx..=node_child_idx, children);
+        self.nodes.remove(node_id);
+
+        true
+    }
+
+    /// Remove every node except the root `Document` node.
+    /// User-agent stylesheets are kept, but stylesheets of removed nodes are dropped.
+    pub fn clear(&mut self) {
+        let root_id = self.root_node().id;
+        self.nodes.retain(|id, _| id == root_id);
+
+        let root = &mut self.nodes[root_id];
+        root.children.clear();
+        *root.layout_children.borrow_mut() = None;
+        *root.paint_children.borrow_mut() = None;
+
+        for (_, sheet) in std::mem::take(&mut self.nodes_to_stylesheet) {
+            self.stylist.remove_stylesheet(sheet, &self.guard.read());
+        }
+
+        self.nodes_to_id.clear();
+        self.snapshots.clear();
+        self.hover_node_id = None;
+        self.focus_node_id = None;
+
+        self.changed.clear();
+        self.changed.insert(root_id);
+    }
+
     pub fn resolve_url(&self, raw: &str) -> url::Url {
         match &self.base_url {
             Some(base_url) => base_url.join(raw).unwrap(),
@@ -592,6 +1112,11 @@ impl Document {
         }
     }
 
+    /// Serialize the whole document to HTML (see [`Node::to_html`])
+    pub fn to_html(&self) -> String {
+        self.root_node().to_html()
+    }
+
     pub fn print_tree(&self) {
         crate::util::walk_tree(0, self.root_node());
     }
@@ -787,6 +1312,98 @@ impl Document {
         }
     }
 
+    /// Set several attributes on an element, replacing existing attributes with the same name.
+    /// The node is only snapshotted and marked for restyle once.
+    pub fn set_attributes(&mut self, node_id: usize, attrs: impl IntoIterator<Item = Attribute>) {
+        if self.nodes[node_id].element_data().is_none() {
+            return;
+        }
+
+        self.snapshot_node(node_id);
+        self.changed.insert(node_id);
+        self.nodes[node_id].set_restyle_hint(RestyleHint::restyle_subtree());
+
+        let mut style_changed = false;
+        for attr in attrs {
+            self.sync_attribute_state(node_id, &attr.name.local, Some(&attr.value));
+            style_changed |= attr.name.local == local_name!("style");
+
+            let element = self.nodes[node_id].element_data_mut().unwrap();
+            let existing_attr = element.attrs.iter_mut().find(|existing| {
+                existing.name.local == attr.name.local && existing.name.ns == attr.name.ns
+            });
+            match existing_attr {
+                Some(existing_attr) => existing_attr.value = attr.value,
+                None => element.attrs.push(attr),
+            }
+        }
+
+        let node = &mut self.nodes[node_id];
+        node.element_data_mut().unwrap().flush_is_focussable();
+        if style_changed {
+            node.flush_style_attribute();
+        }
+    }
+
+    /// Update the state mirroring an attribute (the id map, `<input>` text and checked state).
+    /// Call before setting the attribute to `value`, or removing it if `value` is `None`.
+    pub fn sync_attribute_state(&mut self, node_id: usize, name: &LocalName, value: Option<&str>) {
+        let Some(element) = self.nodes[node_id].element_data_mut() else {
+            return;
+        };
+
+        if *name == local_name!("id") {
+            if let Some(old_id) = element.attr(local_name!("id")) {
+                if self.nodes_to_id.get(old_id) == Some(&node_id) {
+                    self.nodes_to_id.remove(old_id);
+                }
+            }
+            element.id = value.map(Atom::from);
+            if let Some(id) = value {
+                self.nodes_to_id.insert(id.to_string(), node_id);
+            }
+        } else if *name == local_name!("value") {
+            if let Some(input_data) = element.text_input_data_mut() {
+                let text = value.unwrap_or("");
+                input_data.set_text(&mut self.font_ctx, &mut self.layout_ctx, text);
+            }
+        } else if *name == local_name!("checked") {
+            if let Some(checked) = element.checkbox_input_checked_mut() {
+                *checked = value.is_some_and(|value| value != "false");
+            }
+        }
+    }
+
+    /// Replace the content of a text node, re-parsing the stylesheet if its parent is a `<style>`
+    pub fn set_text(
+        &mut self,
+        node_id: usize,
+        text: impl Into<String>,
+    ) -> Result<(), NotATextNode> {
+        let node = &mut self.nodes[node_id];
+        let data = node.raw_dom_data.downcast_text_mut().ok_or(NotATextNode)?;
+
+        let text = text.into();
+        if data.content == text {
+            return Ok(());
+        }
+        data.content = text;
+
+        let parent_id = node.parent;
+        self.changed.insert(node_id);
+        if let Some(parent_id) = parent_id {
+            self.changed.insert(parent_id);
+            if self.nodes[parent_id]
+                .raw_dom_data
+                .is_element_with_tag_name(&local_name!("style"))
+            {
+                self.upsert_stylesheet_for_node(parent_id);
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn snapshot_node_and(&mut self, node_id: usize, cb: impl FnOnce(&mut Node)) {
         self.snapshot_node(node_id);
         cb(&mut self.nodes[node_id]);
@@ -884,11 +1501,7 @@ impl Document {
             }
             // Next is next sibling or parent
             else if let Some(parent) = node.parent_node() {
-                let self_idx = parent
-                    .children
-                    .iter()
-                    .position(|id| *id == node.id)
-                    .unwrap();
+                let self_idx = node.child_index().unwrap();
                 // Next is next sibling
                 if let Some(sibling_id) = parent.children.get(self_idx + 1) {
                     look_in_children = true;
@@ -1211,6 +1824,223 @@ impl Document {
             }
         }
     }
+
+    /// Walk the tree depth-first until `f` returns `ControlFlow::Break`, returning the break value
+    pub fn traverse_depth_first_while<'a, B>(
+        &'a self,
+        mut f: impl FnMut(&'a Node) -> ControlFlow<B>,
+    ) -> Option<B> {
+        let mut stack = vec![self.root_node().id];
+
+        while let Some(node_id) = stack.pop() {
+            let node = &self.nodes[node_id];
+            if let ControlFlow::Break(value) = f(node) {
+                return Some(value);
+            }
+
+            // Push children in reverse so that the first child is popped first
+            stack.extend(node.children.iter().rev());
+        }
+
+        None
+    }
+
+    /// Walk the tree depth-first, skipping nodes more than `max_depth` levels below the root.
+    /// `f` is passed each node along with its depth.
+    pub fn traverse_depth_first_depth_limited(
+        &self,
+        max_depth: usize,
+        mut f: impl FnMut(&Node, usize),
+    ) {
+        let mut stack = vec![(self.root_node().id, 0)];
+
+        while let Some((node_id, depth)) = stack.pop() {
+            let node = &self.nodes[node_id];
+            f(node, depth);
+
+            if depth < max_depth {
+                stack.extend(node.children.iter().rev().map(|id| (*id, depth + 1)));
+            }
+        }
+    }
+
+    /// Walk the tree depth-first, calling [`TreeVisitor::enter`] before a node's descendants and
+    /// [`TreeVisitor::leave`] after them
+    pub fn walk(&self, visitor: &mut impl TreeVisitor) {
+        // The bool records whether the node has already been entered
+        let mut stack = vec![(self.root_node().id, false)];
+
+        while let Some((node_id, entered)) = stack.pop() {
+            let node = &self.nodes[node_id];
+            if entered {
+                visitor.leave(node);
+            } else {
+                visitor.enter(node);
+                stack.push((node_id, true));
+                stack.extend(node.children.iter().rev().map(|id| (*id, false)));
+            }
+        }
+    }
+
+    /// Mutable version of [`Document::traverse_depth_first_while`]. `f` may modify children.
+    pub fn traverse_depth_first_mut_while<B>(
+        &mut self,
+        mut f: impl FnMut(usize, &mut Document) -> ControlFlow<B>,
+    ) -> Option<B> {
+        let mut stack = vec![self.root_node().id];
+
+        while let Some(node_id) = stack.pop() {
+            if let ControlFlow::Break(value) = f(node_id, self) {
+                return Some(value);
+            }
+
+            if let Some(node) = self.nodes.get(node_id) {
+                stack.extend(node.children.iter().rev());
+            }
+        }
+
+        None
+    }
+
+    /// Iterate over all element nodes in the tree, in document order
+    pub fn elements(&self) -> impl Iterator<Item = &Node> {
+        self.root_node()
+            .descendants()
+            .filter(|node| node.is_element())
+    }
+
+    /// Returns the focussable elements in tab order: positive `tabindex`es first (ascending), then
+    /// the rest in document order. Elements with a negative `tabindex` are excluded.
+    pub fn tab_order(&self) -> Vec<&Node> {
+        let mut nodes: Vec<(i32, &Node)> = self
+            .elements()
+            .filter(|node| node.is_focussable())
+            .map(|node| {
+                let tabindex: i32 = node.attr_parsed(local_name!("tabindex")).unwrap_or(0);
+                (tabindex, node)
+            })
+            .collect();
+
+        // A stable sort preserves document order between elements with the same tabindex
+        nodes.sort_by_key(|&(tabindex, _)| if tabindex > 0 { tabindex } else { i32::MAX });
+        nodes.into_iter().map(|(_, node)| node).collect()
+    }
+
+    /// Returns the first node (in document order) which matches `predicate`
+    pub fn find(&self, predicate: impl Fn(&Node) -> bool) -> Option<&Node> {
+        self.traverse_depth_first_while(|node| {
+            if predicate(node) {
+                ControlFlow::Break(node)
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+    }
+
+    /// Returns all nodes which match `predicate`, in document order
+    pub fn find_all(&self, predicate: impl Fn(&Node) -> bool) -> Vec<&Node> {
+        let mut nodes = Vec::new();
+        self.traverse_depth_first_while(|node| {
+            if predicate(node) {
+                nodes.push(node);
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        nodes
+    }
+
+    /// Returns all element nodes with the given tag name, in document order
+    pub fn get_elements_by_tag(&self, tag: &str) -> Vec<&Node> {
+        // Only match true elements (not anonymous blocks, text nodes, comments, etc)
+        self.find_all(|node| match &node.raw_dom_data {
+            NodeData::Element(data) => &*data.name.local == tag,
+            _ => false,
+        })
+    }
+
+    /// Returns the first element which has an attribute with the given name, namespace and value
+    pub fn get_element_by_attribute(&self, name: &QualName, value: &str) -> Option<&Node> {
+        self.find(|node| element_has_attribute(node, name, value))
+    }
+
+    /// Returns all elements which have an attribute with the given name, namespace and value
+    pub fn get_elements_by_attribute(&self, name: &QualName, value: &str) -> Vec<&Node> {
+        self.find_all(|node| element_has_attribute(node, name, value))
+    }
+
+    /// Returns all nodes which are listening for `event`, deepest first (bubbling order)
+    pub fn get_listening_sorted(&self, event: &str) -> Vec<&Node> {
+        self.listening_sorted_by_depth(self.root_node(), |node| node.is_listening_for(event))
+    }
+
+    /// Returns all nodes which are listening for `event`, shallowest first (capturing order)
+    pub fn get_listening_sorted_capture(&self, event: &str) -> Vec<&Node> {
+        let mut listening = self.get_listening_sorted(event);
+        listening.reverse();
+        listening
+    }
+
+    /// Returns the nodes in `root`'s subtree which are listening for `event`, deepest first
+    pub fn get_listening_in_subtree(&self, root: usize, event: &str) -> Vec<&Node> {
+        match self.nodes.get(root) {
+            Some(root) => self.listening_sorted_by_depth(root, |node| node.is_listening_for(event)),
+            None => Vec::new(),
+        }
+    }
+
+    /// Call `f` on `target` and each ancestor which is listening for `event`, until `f` breaks
+    pub fn bubble_event(
+        &self,
+        target: usize,
+        event: &str,
+        mut f: impl FnMut(&Node) -> ControlFlow<()>,
+    ) {
+        let mut maybe_node = self.nodes.get(target);
+        while let Some(node) = maybe_node {
+            if node.is_listening_for(event) && f(node).is_break() {
+                return;
+            }
+            maybe_node = node.parent.map(|id| &self.nodes[id]);
+        }
+    }
+
+    /// Returns all nodes which are listening for any of `events`, deepest first
+    pub fn get_listening_any(&self, events: &[&str]) -> Vec<&Node> {
+        self.listening_sorted_by_depth(self.root_node(), |node| {
+            events.iter().any(|event| node.is_listening_for(event))
+        })
+    }
+
+    /// Returns the number of nodes listening for each event type, including detached nodes
+    pub fn listener_counts(&self) -> HashMap<String, usize> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (_, node) in self.nodes.iter() {
+            let mut seen = HashSet::new();
+            for listener in &node.listeners {
+                if seen.insert(listener.name.as_str()) {
+                    *counts.entry(listener.name.clone()).or_default() += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Collect the nodes in the subtree rooted at `root` that match `filter`, deepest first
+    fn listening_sorted_by_depth<'a>(
+        &'a self,
+        root: &'a Node,
+        filter: impl Fn(&Node) -> bool,
+    ) -> Vec<&'a Node> {
+        let mut listening: Vec<(usize, &Node)> = std::iter::once(root)
+            .chain(root.descendants())
+            .filter(|&node| filter(node))
+            .map(|node| (node.depth(), node))
+            .collect();
+
+        // Stable sort so that nodes at the same depth stay in document order
+        listening.sort_by_key(|&(depth, _)| std::cmp::Reverse(depth));
+        listening.into_iter().map(|(_, node)| node).collect()
+    }
 }
 
 impl AsRef<Document> for Document {
@@ -1224,3 +2054,913 @@ impl AsMut<Document> for Document {
         self
     }
 }
+
+fn element_has_attribute(node: &Node, name: &QualName, value: &str) -> bool {
+    let Some(elem) = node.element_data() else {
+        return false;
+    };
+    elem.attrs
+        .iter()
+        .any(|attr| attr.name.local == name.local && attr.name.ns == name.ns && attr.value == value)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use markup5ever::{namespace_url, ns};
+
+    pub(crate) fn test_document() -> Document {
+        Document::new(Viewport::new(800, 600, 1.0, ColorScheme::Light))
+    }
+
+    /// Create an element and append it to `parent_id`
+    pub(crate) fn element(doc: &mut Document, parent_id: usize, tag: &str) -> usize {
+        element_with_attrs(doc, parent_id, tag, &[])
+    }
+
+    pub(crate) fn element_with_attrs(
+        doc: &mut Document,
+        parent_id: usize,
+        tag: &str,
+        attrs: &[(&str, &str)],
+    ) -> usize {
+        let attrs = attrs
+            .iter()
+            .map(|(name, value)| Attribute {
+                name: QualName::new(None, ns!(), LocalName::from(*name)),
+                value: value.to_string(),
+            })
+            .collect();
+        let name = QualName::new(None, ns!(html), LocalName::from(tag));
+        let node_id = doc.create_element(name, attrs);
+        doc.append_children(parent_id, &[node_id]);
+        node_id
+    }
+
+    /// Create a text node and append it to `parent_id`
+    pub(crate) fn text(doc: &mut Document, parent_id: usize, content: &str) -> usize {
+        let node_id = doc.create_text_node(content);
+        doc.append_children(parent_id, &[node_id]);
+        node_id
+    }
+
+    pub(crate) fn ids(nodes: &[&Node]) -> Vec<usize> {
+        nodes.iter().map(|node| node.id).collect()
+    }
+
+    /// Append a chain of `depth` nested `<div>`s to `parent_id`, returning the outermost one
+    pub(crate) fn deep_chain(doc: &mut Document, parent_id: usize, depth: usize) -> usize {
+        let mut parent_id = parent_id;
+        let mut outermost = None;
+        for _ in 0..depth {
+            let name = QualName::new(None, ns!(html), local_name!("div"));
+            let node_id = doc.create_element(name, Vec::new());
+            doc.nodes[node_id].parent = Some(parent_id);
+            doc.nodes[parent_id].children.push(node_id);
+            outermost.get_or_insert(node_id);
+            parent_id = node_id;
+        }
+        outermost.unwrap()
+    }
+
+    #[test]
+    fn traverse_depth_first_while_visits_in_document_order_and_stops_early() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let div = element(&mut doc, body, "div");
+        let span = element(&mut doc, div, "span");
+        let p = element(&mut doc, body, "p");
+
+        let mut visited = Vec::new();
+        let found = doc.traverse_depth_first_while(|node| {
+            visited.push(node.id);
+            if node.id == p {
+                ControlFlow::Break(node.id)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(found, Some(p));
+        assert_eq!(visited, vec![0, html, body, div, span, p]);
+
+        let mut count = 0;
+        let found = doc.traverse_depth_first_while(|_| {
+            count += 1;
+            ControlFlow::<()>::Continue(())
+        });
+        assert_eq!(found, None);
+        assert_eq!(count, 6);
+    }
+
+    #[test]
+    fn traverse_depth_first_mut_while_sees_children_added_by_callback() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+
+        let mut visited = Vec::new();
+        doc.traverse_depth_first_mut_while(|node_id, doc| {
+            visited.push(node_id);
+            if node_id == html {
+                element(doc, html, "body");
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        assert_eq!(visited.len(), 3);
+    }
+
+    #[test]
+    fn get_elements_by_tag_returns_elements_in_document_order() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let div_a = element(&mut doc, html, "div");
+        let div_b = element(&mut doc, div_a, "div");
+        element(&mut doc, html, "p");
+        text(&mut doc, div_b, "div");
+
+        assert_eq!(ids(&doc.get_elements_by_tag("div")), vec![div_a, div_b]);
+        assert!(doc.get_elements_by_tag("span").is_empty());
+    }
+
+    #[test]
+    fn get_elements_by_attribute_matches_name_and_value() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = element_with_attrs(&mut doc, html, "div", &[("data-role", "item")]);
+        element_with_attrs(&mut doc, html, "div", &[("data-role", "header")]);
+        let c = element_with_attrs(&mut doc, a, "span", &[("data-role", "item")]);
+
+        let name = QualName::new(None, ns!(), LocalName::from("data-role"));
+        assert_eq!(
+            ids(&doc.get_elements_by_attribute(&name, "item")),
+            vec![a, c]
+        );
+        assert_eq!(
+            doc.get_element_by_attribute(&name, "item").map(|n| n.id),
+            Some(a)
+        );
+        assert!(doc.get_element_by_attribute(&name, "footer").is_none());
+
+        let svg_name = QualName::new(None, ns!(svg), LocalName::from("data-role"));
+        assert!(doc.get_element_by_attribute(&svg_name, "item").is_none());
+    }
+
+    #[test]
+    fn move_node_reparents_and_refuses_cycles() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = element(&mut doc, html, "div");
+        let b = element(&mut doc, html, "div");
+        let a_child = element(&mut doc, a, "span");
+        let b_child = element(&mut doc, b, "span");
+
+        assert!(doc.move_node(a_child, b, 0));
+        assert_eq!(doc.nodes[a].children, Vec::<usize>::new());
+        assert_eq!(doc.nodes[b].children, vec![a_child, b_child]);
+        assert_eq!(doc.nodes[a_child].parent, Some(b));
+
+        // Moving within the same parent: the index applies after removal
+        assert!(doc.move_node(a_child, b, 1));
+        assert_eq!(doc.nodes[b].children, vec![b_child, a_child]);
+        // Out of range indices are clamped
+        assert!(doc.move_node(b, html, 100));
+        assert_eq!(doc.nodes[html].children, vec![a, b]);
+
+        // A node can't be moved into itself or its own subtree
+        assert!(!doc.move_node(b, b, 0));
+        assert!(!doc.move_node(b, a_child, 0));
+        assert_eq!(doc.nodes[b].parent, Some(html));
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn insert_before_and_append_keep_their_order() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = element(&mut doc, html, "div");
+        let b = doc.create_text_node("b");
+        let c = doc.create_text_node("c");
+        let d = doc.create_text_node("d");
+
+        doc.insert_before(a, &[b, c]);
+        doc.append(a, &[d]);
+        assert_eq!(doc.nodes[html].children, vec![b, c, a, d]);
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "would create a cycle")]
+    #[cfg(debug_assertions)]
+    fn inserting_an_ancestor_panics_in_debug_builds() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let div = element(&mut doc, body, "div");
+        doc.append(div, &[html]);
+    }
+
+    #[test]
+    fn append_children_appends_in_order_and_sets_parents() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let first = text(&mut doc, html, "first");
+        let a = doc.create_text_node("a");
+        let b = doc.create_text_node("b");
+
+        doc.changed.clear();
+        doc.append_children(html, &[a, b]);
+
+        assert_eq!(doc.nodes[html].children, vec![first, a, b]);
+        assert_eq!(doc.nodes[a].parent, Some(html));
+        assert_eq!(doc.nodes[b].parent, Some(html));
+        assert!(doc.changed.contains(&html));
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn swap_nodes_swaps_positions_across_parents() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let left = element(&mut doc, html, "div");
+        let right = element(&mut doc, html, "div");
+        let a = text(&mut doc, left, "a");
+        let b = text(&mut doc, right, "b");
+        let c = text(&mut doc, right, "c");
+
+        assert!(doc.swap_nodes(a, c));
+        assert_eq!(doc.nodes[left].children, vec![c]);
+        assert_eq!(doc.nodes[right].children, vec![b, a]);
+        assert_eq!(doc.nodes[a].parent, Some(right));
+        assert_eq!(doc.nodes[c].parent, Some(left));
+
+        // Siblings, an ancestor, the node itself and the (parentless) root are handled too
+        assert!(doc.swap_nodes(b, a));
+        assert_eq!(doc.nodes[right].children, vec![a, b]);
+        assert!(!doc.swap_nodes(left, c));
+        assert!(!doc.swap_nodes(a, a));
+        assert!(!doc.swap_nodes(0, b));
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn get_listening_sorted_returns_deepest_nodes_first() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let a = element(&mut doc, body, "div");
+        let b = element(&mut doc, body, "div");
+        let inner = element(&mut doc, a, "span");
+        for id in [html, a, b, inner] {
+            doc.nodes[id].toggle_event_listener("click");
+        }
+        doc.nodes[body].toggle_event_listener("keydown");
+
+        let bubble = doc.get_listening_sorted("click");
+        assert_eq!(ids(&bubble), vec![inner, a, b, html]);
+        let capture = doc.get_listening_sorted_capture("click");
+        assert_eq!(ids(&capture), vec![html, b, a, inner]);
+    }
+
+    #[test]
+    fn deep_clone_node_copies_data_and_listeners() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let div = element_with_attrs(&mut doc, html, "div", &[("id", "original")]);
+        let label = text(&mut doc, div, "label");
+        doc.nodes[div].toggle_event_listener("click");
+
+        let clone = doc.deep_clone_node(div);
+        assert_ne!(clone, div);
+        assert_eq!(doc.nodes[clone].parent, None);
+        assert_eq!(doc.nodes[clone].attr(local_name!("id")), Some("original"));
+        assert!(doc.nodes[clone].is_listening_for("click"));
+
+        let cloned_label = doc.nodes[clone].children[0];
+        assert_ne!(cloned_label, label);
+        assert_eq!(doc.nodes[cloned_label].parent, Some(clone));
+        assert_eq!(doc.nodes[cloned_label].text_content(), "label");
+    }
+
+    #[test]
+    fn get_listening_any_returns_each_node_once() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = element(&mut doc, html, "div");
+        let b = element(&mut doc, a, "div");
+        doc.nodes[a].toggle_event_listener("click");
+        doc.nodes[a].toggle_event_listener("keydown");
+        doc.nodes[b].toggle_event_listener("keydown");
+        doc.nodes[html].toggle_event_listener("scroll");
+
+        let listening = doc.get_listening_any(&["click", "keydown"]);
+        assert_eq!(ids(&listening), vec![b, a]);
+        assert!(doc.get_listening_any(&[]).is_empty());
+    }
+
+    #[test]
+    fn listener_counts_include_detached_nodes() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = element(&mut doc, html, "div");
+        let detached = doc.create_text_node("detached");
+        doc.nodes[html].toggle_event_listener("click");
+        doc.nodes[a].toggle_event_listener("click");
+        doc.nodes[a].toggle_event_listener("keydown");
+        doc.nodes[detached].toggle_event_listener("click");
+
+        let counts = doc.listener_counts();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["click"], 3);
+        assert_eq!(counts["keydown"], 1);
+
+        // Removing every listener (or the node holding it) brings the counts back to zero
+        doc.nodes[html].remove_event_listener("click");
+        doc.nodes[a].remove_event_listener("keydown");
+        assert_eq!(doc.listener_counts()["click"], 2);
+        assert!(!doc.listener_counts().contains_key("keydown"));
+        doc.nodes[a].clear_event_listeners();
+        doc.remove_and_drop_node(detached);
+        assert!(doc.listener_counts().is_empty());
+    }
+
+    #[test]
+    fn find_returns_first_match_and_find_all_returns_every_match() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = text(&mut doc, html, "a");
+        let div = element(&mut doc, html, "div");
+        let b = text(&mut doc, div, "b");
+
+        assert_eq!(
+            doc.find(|node| node.is_text_node()).map(|node| node.id),
+            Some(a)
+        );
+        assert_eq!(ids(&doc.find_all(|node| node.is_text_node())), vec![a, b]);
+        assert!(doc.find(|node| node.id == usize::MAX).is_none());
+        assert!(doc.find_all(|_| false).is_empty());
+    }
+
+    #[test]
+    fn traverse_depth_first_depth_limited_skips_deeper_nodes() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        element(&mut doc, body, "div");
+        let head = element(&mut doc, html, "head");
+
+        let mut visited = Vec::new();
+        doc.traverse_depth_first_depth_limited(2, |node, depth| visited.push((node.id, depth)));
+        assert_eq!(visited, vec![(0, 0), (html, 1), (body, 2), (head, 2)]);
+    }
+
+    #[test]
+    fn remove_and_drop_nodes_handles_parents_and_children_together() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let kept = text(&mut doc, html, "kept");
+        let div = element(&mut doc, html, "div");
+        let inner = text(&mut doc, div, "inner");
+
+        // A detached root along with its child
+        let detached = doc.create_text_node("detached");
+        let detached_root = element(&mut doc, html, "span");
+        doc.remove_node(detached_root);
+        doc.append_children(detached_root, &[detached]);
+
+        doc.changed.clear();
+        doc.remove_and_drop_nodes(&[inner, detached, div, detached_root, inner]);
+
+        for id in [div, inner, detached_root, detached] {
+            assert!(doc.get_node(id).is_none());
+        }
+        assert_eq!(doc.nodes[html].children, vec![kept]);
+        assert!(doc.changed.contains(&html));
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn insert_children_at_detaches_attached_children() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let from = element(&mut doc, html, "div");
+        let to = element(&mut doc, html, "div");
+        let a = text(&mut doc, from, "a");
+        let b = text(&mut doc, to, "b");
+        let c = text(&mut doc, to, "c");
+
+        doc.changed.clear();
+        doc.insert_children_at(to, 1, &[a]);
+        assert_eq!(doc.nodes[from].children, Vec::<usize>::new());
+        assert_eq!(doc.nodes[to].children, vec![b, a, c]);
+        assert!(doc.changed.contains(&from) && doc.changed.contains(&to));
+
+        // Moving within the same parent: the index applies after detaching
+        doc.insert_children_at(to, 0, &[c]);
+        assert_eq!(doc.nodes[to].children, vec![c, b, a]);
+        doc.append_children(to, &[c]);
+        assert_eq!(doc.nodes[to].children, vec![b, a, c]);
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn deep_clone_node_handles_very_deep_trees() {
+        // A recursive clone of a chain this deep would overflow this thread's small stack
+        let handle = std::thread::Builder::new()
+            .stack_size(512 * 1024)
+            .spawn(|| {
+                let mut doc = test_document();
+                let html = element(&mut doc, 0, "html");
+                let top = deep_chain(&mut doc, html, 5_000);
+
+                let clone = doc.deep_clone_node(top);
+                let mut depth = 1;
+                let mut node = &doc.nodes[clone];
+                while let Some(&child_id) = node.children.first() {
+                    assert_eq!(doc.nodes[child_id].parent, Some(node.id));
+                    node = &doc.nodes[child_id];
+                    depth += 1;
+                }
+                assert_eq!(depth, 5_000);
+                assert_eq!(doc.nodes[clone].parent, None);
+            })
+            .unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn set_attributes_replaces_and_adds_attributes() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let div = element_with_attrs(&mut doc, html, "div", &[("id", "old"), ("title", "t")]);
+        let label = text(&mut doc, div, "label");
+        let attr = |name: &str, value: &str| Attribute {
+            name: QualName::new(None, ns!(), LocalName::from(name)),
+            value: value.to_string(),
+        };
+
+        doc.changed.clear();
+        doc.set_attributes(div, [attr("id", "new"), attr("class", "a b")]);
+
+        let element = doc.nodes[div].element_data().unwrap();
+        assert_eq!(element.id.as_deref(), Some("new"));
+        assert_eq!(doc.nodes[div].attr(local_name!("id")), Some("new"));
+        assert_eq!(doc.nodes[div].attr(local_name!("title")), Some("t"));
+        assert_eq!(doc.nodes[div].attr(local_name!("class")), Some("a b"));
+        assert_eq!(doc.nodes[div].attrs().unwrap().len(), 3);
+        assert!(doc.changed.contains(&div));
+
+        // Non-elements are ignored
+        doc.set_attributes(label, [attr("id", "text")]);
+        assert!(doc.nodes[label].attrs().is_none());
+    }
+
+    #[test]
+    fn unwrap_node_promotes_children_into_its_place() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let before = text(&mut doc, html, "before");
+        let wrapper = element(&mut doc, html, "span");
+        let a = text(&mut doc, wrapper, "a");
+        let b = element(&mut doc, wrapper, "b");
+        let after = text(&mut doc, html, "after");
+
+        assert!(doc.unwrap_node(wrapper));
+        assert!(doc.get_node(wrapper).is_none());
+        assert_eq!(doc.nodes[html].children, vec![before, a, b, after]);
+        assert_eq!(doc.nodes[a].parent, Some(html));
+        assert_eq!(doc.nodes[b].parent, Some(html));
+        assert!(!doc.unwrap_node(0));
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn is_descendant_is_strict() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let div = element(&mut doc, body, "div");
+
+        assert!(doc.is_descendant(div, html));
+        assert!(doc.is_descendant(div, 0));
+        assert!(!doc.is_descendant(div, div));
+        assert!(!doc.is_descendant(html, div));
+        assert!(!doc.is_descendant(usize::MAX, html));
+        assert!(doc.nodes[div].is_descendant_of(body));
+    }
+
+    #[test]
+    fn lowest_common_ancestor_finds_the_deepest_shared_ancestor() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let a = element(&mut doc, body, "div");
+        let a_child = text(&mut doc, a, "a");
+        let b = text(&mut doc, body, "b");
+        let head = element(&mut doc, html, "head");
+        let detached = doc.create_text_node("detached");
+
+        assert_eq!(doc.lowest_common_ancestor(a_child, b), Some(body));
+        assert_eq!(doc.lowest_common_ancestor(b, a_child), Some(body));
+        assert_eq!(doc.lowest_common_ancestor(a_child, head), Some(html));
+        assert_eq!(doc.lowest_common_ancestor(a_child, a), Some(a));
+        assert_eq!(doc.lowest_common_ancestor(b, b), Some(b));
+        assert_eq!(doc.lowest_common_ancestor(b, detached), None);
+        assert_eq!(doc.lowest_common_ancestor(b, usize::MAX), None);
+    }
+
+    #[test]
+    fn bubble_event_visits_listening_ancestors_until_stopped() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let div = element(&mut doc, body, "div");
+        let target = text(&mut doc, div, "target");
+        for id in [html, body, target] {
+            doc.nodes[id].toggle_event_listener("click");
+        }
+
+        let mut visited = Vec::new();
+        doc.bubble_event(target, "click", |node| {
+            visited.push(node.id);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(visited, vec![target, body, html]);
+
+        visited.clear();
+        doc.bubble_event(target, "click", |node| {
+            visited.push(node.id);
+            if node.id == body {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(visited, vec![target, body]);
+    }
+
+    #[test]
+    fn node_depth_counts_ancestors() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+
+        assert_eq!(doc.node_depth(0), Some(0));
+        assert_eq!(doc.node_depth(body), Some(2));
+        assert_eq!(doc.node_depth(usize::MAX), None);
+    }
+
+    #[test]
+    fn elements_skips_non_element_nodes() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        text(&mut doc, html, "text");
+        let body = element(&mut doc, html, "body");
+        let comment = doc.create_node(NodeData::Comment);
+        doc.append_children(body, &[comment]);
+        let div = element(&mut doc, body, "div");
+
+        let elements: Vec<usize> = doc.elements().map(|node| node.id).collect();
+        assert_eq!(elements, vec![html, body, div]);
+    }
+
+    #[test]
+    fn clear_removes_everything_but_the_root() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        text(&mut doc, html, "text");
+        doc.create_text_node("detached");
+
+        doc.clear();
+        assert_eq!(doc.nodes.len(), 1);
+        assert!(doc.root_node().children.is_empty());
+        assert!(doc.try_root_element().is_none());
+
+        // The document can be reused afterwards
+        let html = element(&mut doc, 0, "html");
+        assert_eq!(doc.root_element().id, html);
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn tab_order_puts_positive_tabindexes_first() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let button = element(&mut doc, html, "button");
+        let second = element_with_attrs(&mut doc, html, "div", &[("tabindex", "2")]);
+        element_with_attrs(&mut doc, html, "input", &[("tabindex", "-1")]);
+        let zero = element_with_attrs(&mut doc, html, "div", &[("tabindex", "0")]);
+        let first = element_with_attrs(&mut doc, html, "span", &[("tabindex", "1")]);
+        element(&mut doc, html, "div");
+
+        assert_eq!(ids(&doc.tab_order()), vec![first, second, button, zero]);
+    }
+
+    #[test]
+    fn get_listening_in_subtree_is_scoped_to_the_subtree() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let modal = element(&mut doc, html, "div");
+        let button = element(&mut doc, modal, "button");
+        let outside = element(&mut doc, html, "button");
+        for id in [modal, button, outside] {
+            doc.nodes[id].toggle_event_listener("click");
+        }
+
+        assert_eq!(
+            ids(&doc.get_listening_in_subtree(modal, "click")),
+            vec![button, modal]
+        );
+        assert_eq!(
+            ids(&doc.get_listening_in_subtree(button, "click")),
+            vec![button]
+        );
+        assert!(doc.get_listening_in_subtree(usize::MAX, "click").is_empty());
+    }
+
+    #[test]
+    fn set_text_updates_text_nodes_and_style_elements() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let hello = text(&mut doc, html, "hello");
+        let style = element(&mut doc, html, "style");
+        let css = text(&mut doc, style, "");
+
+        doc.changed.clear();
+        assert_eq!(doc.set_text(hello, "world"), Ok(()));
+        assert_eq!(doc.nodes[hello].text_content(), "world");
+        assert!(doc.changed.contains(&hello) && doc.changed.contains(&html));
+        assert_eq!(doc.set_text(html, "nope"), Err(NotATextNode));
+
+        assert!(!doc.nodes_to_stylesheet.contains_key(&style));
+        assert_eq!(doc.set_text(css, "p { color: red }"), Ok(()));
+        assert!(doc.nodes_to_stylesheet.contains_key(&style));
+    }
+
+    #[test]
+    fn validate_insert_reports_why_an_insertion_is_invalid() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let hello = text(&mut doc, body, "hello");
+        let detached = doc.create_text_node("detached");
+
+        assert_eq!(doc.validate_insert(body, detached), Ok(()));
+        assert_eq!(
+            doc.validate_insert(body, usize::MAX),
+            Err(InsertError::MissingNode(usize::MAX))
+        );
+        assert_eq!(
+            doc.validate_insert(hello, detached),
+            Err(InsertError::ParentCannotHaveChildren)
+        );
+        assert_eq!(doc.validate_insert(body, html), Err(InsertError::Cycle));
+        assert_eq!(doc.validate_insert(body, body), Err(InsertError::Cycle));
+        assert_eq!(
+            doc.validate_insert(html, hello),
+            Err(InsertError::AlreadyAttached { parent_id: body })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid insertion")]
+    #[cfg(debug_assertions)]
+    fn appending_to_a_text_node_panics_in_debug_builds() {
+        let mut doc = test_document();
+        let hello = text(&mut doc, 0, "hello");
+        let detached = doc.create_text_node("detached");
+        doc.append_children(hello, &[detached]);
+    }
+
+    #[test]
+    fn move_node_up_and_down_swap_with_siblings() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = text(&mut doc, html, "a");
+        let b = text(&mut doc, html, "b");
+        let c = text(&mut doc, html, "c");
+
+        assert!(doc.move_node_up(c));
+        assert_eq!(doc.nodes[html].children, vec![a, c, b]);
+        assert!(doc.move_node_down(a));
+        assert_eq!(doc.nodes[html].children, vec![c, a, b]);
+        assert!(!doc.move_node_up(c));
+        assert!(!doc.move_node_down(b));
+        assert!(!doc.move_node_up(0));
+    }
+
+    #[test]
+    fn normalize_merges_and_removes_text_nodes() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = text(&mut doc, html, "a");
+        let empty = text(&mut doc, html, "");
+        let b = text(&mut doc, html, "b");
+        let br = element(&mut doc, html, "br");
+        let c = text(&mut doc, html, "c");
+        let style = element(&mut doc, html, "style");
+        let css = text(&mut doc, style, "p {");
+        let css_rest = text(&mut doc, style, " color: red }");
+
+        doc.normalize(html);
+        assert_eq!(doc.nodes[html].children, vec![a, br, c, style]);
+        assert_eq!(doc.nodes[a].text_content(), "ab");
+        assert!(doc.get_node(empty).is_none() && doc.get_node(b).is_none());
+        assert!(doc.get_node(css_rest).is_none());
+        assert_eq!(doc.nodes[css].text_content(), "p { color: red }");
+        assert!(doc.nodes_to_stylesheet.contains_key(&style));
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn walk_calls_enter_and_leave_in_balanced_order() {
+        #[derive(Default)]
+        struct Recorder(Vec<(&'static str, usize)>);
+        impl TreeVisitor for Recorder {
+            fn enter(&mut self, node: &Node) {
+                self.0.push(("enter", node.id));
+            }
+            fn leave(&mut self, node: &Node) {
+                self.0.push(("leave", node.id));
+            }
+        }
+
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = text(&mut doc, html, "a");
+        let b = text(&mut doc, html, "b");
+
+        let mut recorder = Recorder::default();
+        doc.walk(&mut recorder);
+        assert_eq!(
+            recorder.0,
+            vec![
+                ("enter", 0),
+                ("enter", html),
+                ("enter", a),
+                ("leave", a),
+                ("enter", b),
+                ("leave", b),
+                ("leave", html),
+                ("leave", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn replace_children_detaches_old_children() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let old = text(&mut doc, html, "old");
+        let kept = text(&mut doc, html, "kept");
+        let new = doc.create_text_node("new");
+
+        doc.replace_children(html, &[new, kept]);
+        assert_eq!(doc.nodes[html].children, vec![new, kept]);
+        assert_eq!(doc.nodes[old].parent, None);
+        assert_eq!(doc.nodes[kept].parent, Some(html));
+        assert_eq!(doc.nodes[new].parent, Some(html));
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn create_element_initialises_style_data_and_ids() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let div = element_with_attrs(&mut doc, html, "div", &[("id", "main")]);
+
+        assert_eq!(doc.nodes[div].parent, Some(html));
+        assert!(doc.nodes[div].stylo_element_data.borrow().is_some());
+        assert_eq!(doc.nodes_to_id.get("main"), Some(&div));
+    }
+
+    #[test]
+    fn clone_subtree_from_copies_between_documents() {
+        let mut source = test_document();
+        let html = element(&mut source, 0, "html");
+        let div = element_with_attrs(&mut source, html, "div", &[("style", "color: red")]);
+        text(&mut source, div, "hello");
+        source.nodes[div].toggle_event_listener("click");
+
+        let mut doc = test_document();
+        let clone = doc.clone_subtree_from(&source, div);
+        assert_eq!(doc.nodes[clone].parent, None);
+        assert!(doc.nodes[clone].is_listening_for("click"));
+        assert_eq!(doc.nodes[clone].text_content(), "hello");
+        let style_attribute = &doc.nodes[clone].element_data().unwrap().style_attribute;
+        assert!(style_attribute.is_some());
+
+        doc.append_children(0, &[clone]);
+        assert_eq!(doc.to_html(), "<div style=\"color: red\">hello</div>");
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn check_invariants_detects_broken_links() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = text(&mut doc, html, "a");
+        let b = text(&mut doc, html, "b");
+        assert_eq!(doc.check_invariants(), Ok(()));
+
+        doc.nodes[a].parent = None;
+        assert_eq!(
+            doc.check_invariants(),
+            Err(InvariantViolation::ParentMismatch {
+                parent_id: html,
+                child_id: a
+            })
+        );
+        doc.nodes[a].parent = Some(html);
+
+        doc.nodes[html].children.retain(|&id| id != b);
+        assert_eq!(
+            doc.check_invariants(),
+            Err(InvariantViolation::NotInParentChildren {
+                node_id: b,
+                parent_id: html
+            })
+        );
+        doc.nodes[html].children.extend([b, b]);
+        assert_eq!(
+            doc.check_invariants(),
+            Err(InvariantViolation::DuplicateChild {
+                parent_id: html,
+                child_id: b
+            })
+        );
+    }
+
+    #[test]
+    fn check_invariants_ignores_nodes_created_by_layout() {
+        let mut doc = test_document();
+        doc.add_user_agent_stylesheet(
+            r#"html, body, div, p { display: block } p::before { content: "x" }"#,
+        );
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let div = element(&mut doc, body, "div");
+        // Inline content next to a block gets wrapped in an anonymous block
+        text(&mut doc, div, "inline");
+        let p = element(&mut doc, div, "p");
+        text(&mut doc, p, "paragraph");
+
+        doc.resolve();
+        assert!(doc.nodes[p].before.is_some());
+        let anonymous_blocks = doc
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.raw_dom_data.is_anonymous_block())
+            .count();
+        assert!(anonymous_blocks >= 2);
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn insert_children_at_ignores_duplicate_ids() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = text(&mut doc, html, "a");
+        let detached = doc.create_text_node("detached");
+
+        doc.append_children(html, &[detached, detached]);
+        assert_eq!(doc.nodes[html].children, vec![a, detached]);
+
+        doc.insert_children_at(html, 0, &[detached, a, detached]);
+        assert_eq!(doc.nodes[html].children, vec![detached, a]);
+        assert_eq!(doc.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn set_attributes_syncs_ids_and_input_state() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let div = element_with_attrs(&mut doc, html, "div", &[("id", "old")]);
+        let checkbox = element_with_attrs(&mut doc, html, "input", &[("type", "checkbox")]);
+        let input = element(&mut doc, html, "input");
+        let specific_data = |doc: &mut Document, id: usize, data: NodeSpecificData| {
+            doc.nodes[id].element_data_mut().unwrap().node_specific_data = data;
+        };
+        specific_data(&mut doc, checkbox, NodeSpecificData::CheckboxInput(false));
+        let text_input = NodeSpecificData::TextInput(crate::node::TextInputData::new(false));
+        specific_data(&mut doc, input, text_input);
+        let attr = |name: &str, value: &str| Attribute {
+            name: QualName::new(None, ns!(), LocalName::from(name)),
+            value: value.to_string(),
+        };
+
+        doc.set_attributes(div, [attr("id", "new")]);
+        assert_eq!(doc.nodes_to_id.get("new"), Some(&div));
+        assert!(!doc.nodes_to_id.contains_key("old"));
+
+        doc.set_attributes(checkbox, [attr("checked", "")]);
+        let checkbox_data = doc.nodes[checkbox].element_data().unwrap();
+        assert_eq!(checkbox_data.checkbox_input_checked(), Some(true));
+        doc.set_attributes(checkbox, [attr("checked", "false")]);
+        let checkbox_data = doc.nodes[checkbox].element_data().unwrap();
+        assert_eq!(checkbox_data.checkbox_input_checked(), Some(false));
+
+        doc.set_attributes(input, [attr("value", "hello")]);
+        let input_data = doc.nodes[input].element_data().unwrap().text_input_data();
+        assert!(input_data.unwrap().editor.text() == "hello");
+    }
+}
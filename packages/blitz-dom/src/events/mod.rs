@@ -4,6 +4,7 @@ pub(crate) use keyboard::apply_keypress_event;
 
 use winit::event::{Ime, KeyEvent, Modifiers};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EventListener {
     pub name: String,
 }
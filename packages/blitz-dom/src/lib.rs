@@ -9,6 +9,7 @@
 //! ## Feature flags
 //!  - `default`: Enables the features listed below.
 //!  - `tracing`: Enables tracing support.
+//!  - `serde`: Implements `Serialize` for [`DocumentSnapshot`](snapshot::DocumentSnapshot) (not enabled by default).
 
 pub const DEFAULT_CSS: &str = include_str!("../assets/default.css");
 pub(crate) const BULLET_FONT: &[u8] = include_bytes!("../assets/moz-bullet-font.otf");
@@ -44,7 +45,11 @@ pub mod net;
 
 pub mod renderer;
 
-pub use document::{Document, DocumentLike};
+pub mod snapshot;
+
+pub use document::{
+    Document, DocumentLike, InsertError, InvariantViolation, NotATextNode, TreeVisitor,
+};
 pub use markup5ever::{
     local_name, namespace_prefix, namespace_url, ns, Namespace, NamespaceStaticSet, Prefix,
     PrefixStaticSet, QualName,
@@ -52,5 +57,6 @@ pub use markup5ever::{
 pub use node::{ElementNodeData, Node, NodeData, TextNodeData};
 pub use parley::FontContext;
 pub use renderer::{BlitzWindowHandle, DocumentRenderer};
+pub use snapshot::{DocumentSnapshot, NodeDiff, NodeSnapshot};
 pub use string_cache::Atom;
 pub use style::invalidation::element::restyle_hints::RestyleHint;
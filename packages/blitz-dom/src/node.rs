@@ -226,6 +226,7 @@ impl Node {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum NodeKind {
     Document,
     Element,
@@ -278,6 +279,37 @@ impl NodeData {
         }
     }
 
+    pub fn downcast_text(&self) -> Option<&TextNodeData> {
+        match self {
+            Self::Text(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn downcast_text_mut(&mut self) -> Option<&mut TextNodeData> {
+        match self {
+            Self::Text(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a true element (anonymous blocks are not included)
+    pub fn is_element(&self) -> bool {
+        matches!(self, Self::Element(_))
+    }
+
+    pub fn is_anonymous_block(&self) -> bool {
+        matches!(self, Self::AnonymousBlock(_))
+    }
+
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Text(_))
+    }
+
+    pub fn is_comment(&self) -> bool {
+        matches!(self, Self::Comment)
+    }
+
     pub fn is_element_with_tag_name(&self, name: &impl PartialEq<LocalName>) -> bool {
         let Some(elem) = self.downcast_element() else {
             return false;
@@ -755,6 +787,11 @@ impl Node {
             .position(|id| *id == self.id)
     }
 
+    /// Get the nth child of this node
+    pub fn nth_child(&self, n: usize) -> Option<&Node> {
+        self.children.get(n).map(|id| self.with(*id))
+    }
+
     // Get the nth node in the parents child list
     pub fn forward(&self, n: usize) -> Option<&Node> {
         let child_idx = self.child_index().unwrap_or(0);
@@ -776,6 +813,121 @@ impl Node {
             .map(|id| self.with(*id))
     }
 
+    /// Iterate over the ancestors of this node, starting with its parent (the root is not included)
+    pub fn ancestors(&self) -> impl Iterator<Item = &Node> {
+        let mut current = self.parent.map(|id| self.with(id));
+        std::iter::from_fn(move || {
+            let node = current?;
+            // Stop once we reach the root
+            let parent_id = node.parent?;
+            current = Some(node.with(parent_id));
+            Some(node)
+        })
+    }
+
+    /// The number of ancestors this node has (the root node has depth 0)
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut maybe_id = self.parent;
+        while let Some(id) = maybe_id {
+            depth += 1;
+            maybe_id = self.with(id).parent;
+        }
+        depth
+    }
+
+    /// Fold over this node and each of its ancestors in turn, up to and including the root node
+    pub fn fold_ancestors<B>(&self, init: B, mut f: impl FnMut(B, &Node) -> B) -> B {
+        let mut acc = f(init, self);
+        let mut maybe_id = self.parent;
+        while let Some(id) = maybe_id {
+            let node = self.with(id);
+            acc = f(acc, node);
+            maybe_id = node.parent;
+        }
+        acc
+    }
+
+    /// Whether this node is a (strict) descendant of the node with id `ancestor_id`
+    pub fn is_descendant_of(&self, ancestor_id: usize) -> bool {
+        let mut maybe_id = self.parent;
+        while let Some(id) = maybe_id {
+            if id == ancestor_id {
+                return true;
+            }
+            maybe_id = self.with(id).parent;
+        }
+        false
+    }
+
+    /// Lazily iterate over all descendants of this node (but not the node itself) in document order
+    pub fn descendants(&self) -> impl Iterator<Item = &Node> {
+        let mut stack: Vec<usize> = self.children.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let node = self.with(stack.pop()?);
+            stack.extend(node.children.iter().rev());
+            Some(node)
+        })
+    }
+
+    /// Iterate over the siblings that come after this node, in document order
+    pub fn following_siblings(&self) -> impl Iterator<Item = &Node> {
+        let siblings = self.sibling_ids();
+        let start = self.child_index().map_or(siblings.len(), |idx| idx + 1);
+        siblings[start..].iter().map(|id| self.with(*id))
+    }
+
+    /// Iterate over the siblings that come before this node, closest sibling first
+    pub fn preceding_siblings(&self) -> impl Iterator<Item = &Node> {
+        let siblings = self.sibling_ids();
+        let end = self.child_index().unwrap_or(0);
+        siblings[..end].iter().rev().map(|id| self.with(*id))
+    }
+
+    /// The child list of this node's parent (which includes this node)
+    fn sibling_ids(&self) -> &[usize] {
+        match self.parent {
+            Some(parent_id) => &self.with(parent_id).children,
+            None => &[],
+        }
+    }
+
+    /// Whether the node has an event listener for `event`
+    pub fn is_listening_for(&self, event: &str) -> bool {
+        self.listeners.iter().any(|listener| listener.name == event)
+    }
+
+    /// Add an event listener for `event`, or remove it if there already is one.
+    /// Returns whether the node is listening for `event` afterwards.
+    pub fn toggle_event_listener(&mut self, event: &str) -> bool {
+        if self.is_listening_for(event) {
+            self.remove_event_listener(event);
+            false
+        } else {
+            self.add_event_listener(event);
+            true
+        }
+    }
+
+    /// Add an event listener for `event`, unless the node is already listening for it
+    pub fn add_event_listener(&mut self, event: &str) {
+        if !self.is_listening_for(event) {
+            self.listeners.push(EventListener {
+                name: event.to_string(),
+            });
+        }
+    }
+
+    /// Remove the node's event listener for `event`, if it has one
+    pub fn remove_event_listener(&mut self, event: &str) {
+        self.listeners.retain(|listener| listener.name != event);
+    }
+
+    /// Remove all of the node's event listeners
+    pub fn clear_event_listeners(&mut self) {
+        self.listeners.clear();
+    }
+
     pub fn is_element(&self) -> bool {
         matches!(self.raw_dom_data, NodeData::Element { .. })
     }
@@ -880,6 +1032,10 @@ impl Node {
 
                 for attr in data.attrs() {
                     writer.push(' ');
+                    if let Some(prefix) = &attr.name.prefix {
+                        writer.push_str(prefix);
+                        writer.push(':');
+                    }
                     writer.push_str(&attr.name.local);
                     writer.push_str("=\"");
                     #[allow(clippy::unnecessary_unwrap)] // Convert to if-let chain once stabilised
@@ -912,6 +1068,80 @@ impl Node {
         }
     }
 
+    /// Serialize this node and its descendants to HTML. Unlike [`Node::outer_html`], text and
+    /// attribute values are escaped and void elements have no closing tag.
+    pub fn to_html(&self) -> String {
+        let mut output = String::new();
+        self.write_html(&mut output);
+        output
+    }
+
+    pub fn write_html(&self, writer: &mut String) {
+        match &self.raw_dom_data {
+            NodeData::Document | NodeData::AnonymousBlock(_) => {
+                for &child_id in &self.children {
+                    self.with(child_id).write_html(writer);
+                }
+            }
+            NodeData::Comment => writer.push_str("<!---->"),
+            NodeData::Text(data) => {
+                // The contents of raw text elements must not be escaped
+                let in_raw_text_element = self.parent.is_some_and(|parent_id| {
+                    let parent = &self.with(parent_id).raw_dom_data;
+                    parent.is_element_with_tag_name(&local_name!("style"))
+                        || parent.is_element_with_tag_name(&local_name!("script"))
+                });
+                if in_raw_text_element {
+                    writer.push_str(&data.content);
+                } else {
+                    html_escape::encode_text_to_string(&data.content, writer);
+                }
+            }
+            NodeData::Element(data) => {
+                writer.push('<');
+                writer.push_str(&data.name.local);
+                for attr in data.attrs() {
+                    writer.push(' ');
+                    if let Some(prefix) = &attr.name.prefix {
+                        writer.push_str(prefix);
+                        writer.push(':');
+                    }
+                    writer.push_str(&attr.name.local);
+                    writer.push_str("=\"");
+                    html_escape::encode_double_quoted_attribute_to_string(&attr.value, writer);
+                    writer.push('"');
+                }
+                writer.push('>');
+
+                const VOID_ELEMENTS: [LocalName; 13] = [
+                    local_name!("area"),
+                    local_name!("base"),
+                    local_name!("br"),
+                    local_name!("col"),
+                    local_name!("embed"),
+                    local_name!("hr"),
+                    local_name!("img"),
+                    local_name!("input"),
+                    local_name!("link"),
+                    local_name!("meta"),
+                    local_name!("source"),
+                    local_name!("track"),
+                    local_name!("wbr"),
+                ];
+                if VOID_ELEMENTS.contains(&data.name.local) {
+                    return;
+                }
+
+                for &child_id in &self.children {
+                    self.with(child_id).write_html(writer);
+                }
+                writer.push_str("</");
+                writer.push_str(&data.name.local);
+                writer.push('>');
+            }
+        }
+    }
+
     pub fn attrs(&self) -> Option<&[Attribute]> {
         Some(&self.element_data()?.attrs)
     }
@@ -921,6 +1151,37 @@ impl Node {
         Some(&attr.value)
     }
 
+    /// Parse the value of an attribute, returning `None` if it is missing or fails to parse
+    pub fn attr_parsed<T: FromStr>(&self, name: LocalName) -> Option<T> {
+        self.element_data()?.attr_parsed(name)
+    }
+
+    /// Get an attribute's value from this node or the nearest ancestor which has it (e.g. `lang`)
+    pub fn inherited_attr(&self, name: LocalName) -> Option<&str> {
+        let mut node = self;
+        loop {
+            if let Some(value) = node.attr(name.clone()) {
+                return Some(value);
+            }
+            node = self.with(node.parent?);
+        }
+    }
+
+    /// Iterate over the node's `data-*` attributes, with the `data-` prefix stripped from the keys
+    pub fn dataset(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.attrs()
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|attr| Some((attr.name.local.strip_prefix("data-")?, &*attr.value)))
+    }
+
+    /// Get the value of the `data-{key}` attribute
+    pub fn data(&self, key: &str) -> Option<&str> {
+        self.dataset()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+    }
+
     pub fn primary_styles(&self) -> Option<AtomicRef<'_, ComputedValues>> {
         let stylo_element_data = self.stylo_element_data.borrow();
         if stylo_element_data
@@ -1078,6 +1339,13 @@ impl PartialEq for Node {
 
 impl Eq for Node {}
 
+/// Nodes are hashed by id, consistent with their `PartialEq` impl
+impl std::hash::Hash for Node {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_usize(self.id)
+    }
+}
+
 impl std::fmt::Debug for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // FIXME: update to reflect changes to fields
@@ -1095,3 +1363,246 @@ impl std::fmt::Debug for Node {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Attribute, NodeData};
+    use crate::document::tests::{element, element_with_attrs, test_document, text};
+    use markup5ever::{local_name, namespace_url, ns, Prefix, QualName};
+
+    #[test]
+    fn ancestors_start_at_parent_and_exclude_root() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let div = element(&mut doc, body, "div");
+
+        let ancestors: Vec<usize> = doc.nodes[div].ancestors().map(|node| node.id).collect();
+        assert_eq!(ancestors, vec![body, html]);
+        assert_eq!(doc.nodes[0].ancestors().count(), 0);
+    }
+
+    #[test]
+    fn descendants_are_lazy_and_in_document_order() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let head = element(&mut doc, html, "head");
+        let title = element(&mut doc, head, "title");
+        let body = element(&mut doc, html, "body");
+        let p = text(&mut doc, body, "hello");
+
+        let descendants: Vec<usize> = doc.nodes[html].descendants().map(|node| node.id).collect();
+        assert_eq!(descendants, vec![head, title, body, p]);
+        assert_eq!(
+            doc.nodes[html].descendants().nth(1).map(|node| node.id),
+            Some(title)
+        );
+        assert_eq!(doc.nodes[p].descendants().count(), 0);
+    }
+
+    #[test]
+    fn attr_parsed_parses_or_returns_none() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let input = element_with_attrs(
+            &mut doc,
+            html,
+            "input",
+            &[("width", "42"), ("tabindex", "x")],
+        );
+
+        let node = &doc.nodes[input];
+        assert_eq!(node.attr_parsed::<u32>(local_name!("width")), Some(42));
+        assert_eq!(node.attr_parsed::<i32>(local_name!("tabindex")), None);
+        assert_eq!(node.attr_parsed::<u32>(local_name!("height")), None);
+        assert_eq!(doc.nodes[0].attr_parsed::<u32>(local_name!("width")), None);
+    }
+
+    #[test]
+    fn sibling_iterators_walk_outwards_from_the_node() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = text(&mut doc, html, "a");
+        let b = text(&mut doc, html, "b");
+        let c = text(&mut doc, html, "c");
+
+        let following: Vec<usize> = doc.nodes[a]
+            .following_siblings()
+            .map(|node| node.id)
+            .collect();
+        assert_eq!(following, vec![b, c]);
+        let preceding: Vec<usize> = doc.nodes[c]
+            .preceding_siblings()
+            .map(|node| node.id)
+            .collect();
+        assert_eq!(preceding, vec![b, a]);
+        assert_eq!(doc.nodes[c].following_siblings().count(), 0);
+        assert_eq!(doc.nodes[0].following_siblings().count(), 0);
+        assert_eq!(doc.nodes[0].preceding_siblings().count(), 0);
+    }
+
+    #[test]
+    fn nth_child_indexes_into_children() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let a = text(&mut doc, html, "a");
+        let b = text(&mut doc, html, "b");
+
+        assert_eq!(doc.nodes[html].nth_child(0).map(|node| node.id), Some(a));
+        assert_eq!(doc.nodes[html].nth_child(1).map(|node| node.id), Some(b));
+        assert!(doc.nodes[html].nth_child(2).is_none());
+        assert_eq!(doc.nodes[b].child_index(), Some(1));
+    }
+
+    #[test]
+    fn to_html_escapes_text_and_keeps_attribute_prefixes() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let p = element_with_attrs(&mut doc, html, "p", &[("title", "a \"b\"")]);
+        text(&mut doc, p, "1 < 2");
+        element(&mut doc, p, "br");
+        let style = element(&mut doc, html, "style");
+        text(&mut doc, style, "a > b {}");
+        let a = element(&mut doc, html, "a");
+        doc.nodes[a]
+            .element_data_mut()
+            .unwrap()
+            .attrs
+            .push(Attribute {
+                name: QualName::new(Some(Prefix::from("xlink")), ns!(xlink), local_name!("href")),
+                value: "#target".to_string(),
+            });
+
+        assert_eq!(
+            doc.to_html(),
+            "<html><p title=\"a &quot;b&quot;\">1 &lt; 2<br></p><style>a > b {}</style>\
+             <a xlink:href=\"#target\"></a></html>"
+        );
+    }
+
+    #[test]
+    fn toggle_event_listener_adds_and_removes_listeners() {
+        let mut doc = test_document();
+        let div = element(&mut doc, 0, "div");
+        let node = &mut doc.nodes[div];
+
+        assert!(node.toggle_event_listener("click"));
+        assert!(node.toggle_event_listener("keydown"));
+        assert!(node.is_listening_for("click"));
+        assert!(!node.toggle_event_listener("click"));
+        assert!(!node.is_listening_for("click"));
+        assert!(node.is_listening_for("keydown"));
+    }
+
+    #[test]
+    fn add_and_remove_event_listener_are_idempotent() {
+        let mut doc = test_document();
+        let div = element(&mut doc, 0, "div");
+        let node = &mut doc.nodes[div];
+
+        node.add_event_listener("click");
+        node.add_event_listener("click");
+        assert_eq!(node.listeners.len(), 1);
+        assert!(node.is_listening_for("click"));
+
+        node.remove_event_listener("click");
+        node.remove_event_listener("click");
+        assert!(node.listeners.is_empty());
+    }
+
+    #[test]
+    fn node_data_predicates_match_their_kind() {
+        let mut doc = test_document();
+        let div = element(&mut doc, 0, "div");
+        let hello = text(&mut doc, div, "hello");
+
+        let div_data = &doc.nodes[div].raw_dom_data;
+        assert!(div_data.is_element() && !div_data.is_text() && !div_data.is_comment());
+        assert!(div_data.downcast_text().is_none());
+
+        let text_data = &mut doc.nodes[hello].raw_dom_data;
+        assert!(text_data.is_text() && !text_data.is_element() && !text_data.is_anonymous_block());
+        text_data.downcast_text_mut().unwrap().content.push('!');
+        assert_eq!(text_data.downcast_text().unwrap().content, "hello!");
+        assert!(NodeData::Comment.is_comment());
+    }
+
+    #[test]
+    #[allow(clippy::mutable_key_type)] // The hash only uses the node's id, which never changes
+    fn nodes_hash_by_id() {
+        let mut doc = test_document();
+        let a = text(&mut doc, 0, "same");
+        let b = text(&mut doc, 0, "same");
+
+        let set: std::collections::HashSet<&super::Node> =
+            [&doc.nodes[a], &doc.nodes[b], &doc.nodes[a]]
+                .into_iter()
+                .collect();
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&doc.nodes[b]));
+    }
+
+    #[test]
+    fn fold_ancestors_includes_the_node_and_the_root() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+
+        let path = doc.nodes[body].fold_ancestors(Vec::new(), |mut path, node| {
+            path.push(node.id);
+            path
+        });
+        assert_eq!(path, vec![body, html, 0]);
+        assert_eq!(
+            doc.nodes[body].fold_ancestors(0, |n, _| n + 1),
+            doc.nodes[body].depth() + 1
+        );
+    }
+
+    #[test]
+    fn inherited_attr_falls_back_to_ancestors() {
+        let mut doc = test_document();
+        let html = element_with_attrs(&mut doc, 0, "html", &[("lang", "en")]);
+        let div = element_with_attrs(&mut doc, html, "div", &[("lang", "fr")]);
+        let span = element(&mut doc, div, "span");
+        let p = element(&mut doc, html, "p");
+
+        assert_eq!(
+            doc.nodes[span].inherited_attr(local_name!("lang")),
+            Some("fr")
+        );
+        assert_eq!(doc.nodes[p].inherited_attr(local_name!("lang")), Some("en"));
+        assert_eq!(doc.nodes[p].inherited_attr(local_name!("dir")), None);
+    }
+
+    #[test]
+    fn clear_event_listeners_removes_every_listener() {
+        let mut doc = test_document();
+        let div = element(&mut doc, 0, "div");
+        let node = &mut doc.nodes[div];
+        node.toggle_event_listener("click");
+        node.toggle_event_listener("keydown");
+
+        node.clear_event_listeners();
+        assert!(node.listeners.is_empty());
+        assert!(!node.is_listening_for("click"));
+    }
+
+    #[test]
+    fn dataset_strips_the_data_prefix() {
+        let mut doc = test_document();
+        let div = element_with_attrs(
+            &mut doc,
+            0,
+            "div",
+            &[("data-user-id", "7"), ("id", "x"), ("data-", "empty")],
+        );
+
+        let node = &doc.nodes[div];
+        let dataset: Vec<(&str, &str)> = node.dataset().collect();
+        assert_eq!(dataset, vec![("user-id", "7"), ("", "empty")]);
+        assert_eq!(node.data("user-id"), Some("7"));
+        assert_eq!(node.data("id"), None);
+        assert_eq!(doc.nodes[0].dataset().count(), 0);
+    }
+}
@@ -0,0 +1,388 @@
+//! Owned, `Send + Sync` snapshots of a [`Document`]'s tree structure (without style or layout).
+//! With the `serde` feature enabled they can be serialized, e.g. for golden-file tests.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::node::NodeKind;
+use crate::{Document, Node, NodeData};
+
+/// An owned copy of the structure of a [`Document`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DocumentSnapshot {
+    /// The id of the root node
+    pub root: usize,
+    /// All nodes reachable from the root, keyed by node id
+    pub nodes: BTreeMap<usize, NodeSnapshot>,
+}
+
+/// An owned copy of a single [`Node`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NodeSnapshot {
+    pub id: usize,
+    pub kind: NodeKind,
+    /// The tag name (elements only)
+    pub tag: Option<String>,
+    /// The tag namespace (elements only)
+    pub namespace: Option<String>,
+    /// The element's attributes, sorted by name then namespace
+    pub attrs: Vec<AttributeSnapshot>,
+    /// The textual content (text nodes only)
+    pub text: Option<String>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+/// An owned copy of an element attribute
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AttributeSnapshot {
+    pub name: String,
+    pub namespace: String,
+    pub value: String,
+}
+
+// Snapshots must stay plain owned data so that they can be sent across threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<DocumentSnapshot>();
+};
+
+impl DocumentSnapshot {
+    pub fn get(&self, node_id: usize) -> Option<&NodeSnapshot> {
+        self.nodes.get(&node_id)
+    }
+
+    pub fn root_node(&self) -> &NodeSnapshot {
+        &self.nodes[&self.root]
+    }
+
+    pub fn parent(&self, node_id: usize) -> Option<&NodeSnapshot> {
+        self.get(self.get(node_id)?.parent?)
+    }
+
+    /// Iterate over the children of a node, in document order
+    pub fn children(&self, node_id: usize) -> impl Iterator<Item = &NodeSnapshot> {
+        self.get(node_id)
+            .into_iter()
+            .flat_map(|node| node.children.iter())
+            .filter_map(|id| self.get(*id))
+    }
+
+    /// Iterate over the ancestors of a node, starting with its parent (the root is not included)
+    pub fn ancestors(&self, node_id: usize) -> impl Iterator<Item = &NodeSnapshot> {
+        let mut current = self.parent(node_id);
+        std::iter::from_fn(move || {
+            let node = current?;
+            // Stop once we reach the root
+            let parent_id = node.parent?;
+            current = self.get(parent_id);
+            Some(node)
+        })
+    }
+
+    /// Iterate over all descendants of a node (excluding the node itself) in document order
+    pub fn descendants(&self, node_id: usize) -> impl Iterator<Item = &NodeSnapshot> {
+        let mut stack: Vec<usize> = self
+            .get(node_id)
+            .map(|node| node.children.iter().rev().copied().collect())
+            .unwrap_or_default();
+        std::iter::from_fn(move || loop {
+            let node = self.get(stack.pop()?);
+            if let Some(node) = node {
+                stack.extend(node.children.iter().rev());
+                return Some(node);
+            }
+        })
+    }
+}
+
+/// A difference between two [`DocumentSnapshot`]s, as returned by [`DocumentSnapshot::diff`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum NodeDiff {
+    /// The node only exists in the new snapshot
+    Added { id: usize },
+    /// The node only exists in the old snapshot
+    Removed { id: usize },
+    /// The node id exists in both snapshots, but refers to a different kind of node or tag
+    Replaced { id: usize },
+    /// The node exists in both snapshots, but its attributes, text or children have changed
+    Changed {
+        id: usize,
+        attrs: Vec<AttributeDiff>,
+        text_changed: bool,
+        children_changed: bool,
+    },
+}
+
+/// A change to a single attribute. `old` is `None` if it was added and `new` if it was removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AttributeDiff {
+    pub name: String,
+    pub namespace: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl DocumentSnapshot {
+    /// Compute the differences from this snapshot to `other`, ordered by node id
+    pub fn diff(&self, other: &DocumentSnapshot) -> Vec<NodeDiff> {
+        let ids: BTreeSet<usize> = self
+            .nodes
+            .keys()
+            .chain(other.nodes.keys())
+            .copied()
+            .collect();
+
+        ids.into_iter()
+            .filter_map(|id| match (self.get(id), other.get(id)) {
+                (Some(old), Some(new)) => old.diff(new),
+                (Some(_), None) => Some(NodeDiff::Removed { id }),
+                (None, Some(_)) => Some(NodeDiff::Added { id }),
+                (None, None) => None,
+            })
+            .collect()
+    }
+}
+
+impl NodeSnapshot {
+    fn diff(&self, new: &NodeSnapshot) -> Option<NodeDiff> {
+        let id = self.id;
+        if self.kind != new.kind || self.tag != new.tag || self.namespace != new.namespace {
+            return Some(NodeDiff::Replaced { id });
+        }
+
+        let attr_map = |node: &NodeSnapshot| -> BTreeMap<(String, String), String> {
+            node.attrs
+                .iter()
+                .map(|attr| {
+                    let key = (attr.name.clone(), attr.namespace.clone());
+                    (key, attr.value.clone())
+                })
+                .collect()
+        };
+        let mut old_attrs = attr_map(self);
+        let new_attrs = attr_map(new);
+
+        let mut attrs = Vec::new();
+        for ((name, namespace), new_value) in new_attrs {
+            let old_value = old_attrs.remove(&(name.clone(), namespace.clone()));
+            if old_value.as_ref() != Some(&new_value) {
+                attrs.push(AttributeDiff {
+                    name,
+                    namespace,
+                    old: old_value,
+                    new: Some(new_value),
+                });
+            }
+        }
+        // Any attributes left over have been removed
+        for ((name, namespace), old_value) in old_attrs {
+            attrs.push(AttributeDiff {
+                name,
+                namespace,
+                old: Some(old_value),
+                new: None,
+            });
+        }
+        attrs.sort_by(|a, b| (&a.name, &a.namespace).cmp(&(&b.name, &b.namespace)));
+
+        let text_changed = self.text != new.text;
+        let children_changed = self.children != new.children;
+        if attrs.is_empty() && !text_changed && !children_changed {
+            return None;
+        }
+
+        Some(NodeDiff::Changed {
+            id,
+            attrs,
+            text_changed,
+            children_changed,
+        })
+    }
+
+    fn from_node(node: &Node) -> Self {
+        let element = match &node.raw_dom_data {
+            NodeData::Element(data) | NodeData::AnonymousBlock(data) => Some(data),
+            _ => None,
+        };
+
+        let mut attrs: Vec<AttributeSnapshot> = element
+            .map(|data| {
+                data.attrs
+                    .iter()
+                    .map(|attr| AttributeSnapshot {
+                        name: attr.name.local.to_string(),
+                        namespace: attr.name.ns.to_string(),
+                        value: attr.value.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        attrs.sort();
+
+        Self {
+            id: node.id,
+            kind: node.raw_dom_data.kind(),
+            tag: element.map(|data| data.name.local.to_string()),
+            namespace: element.map(|data| data.name.ns.to_string()),
+            attrs,
+            text: node.text_data().map(|data| data.content.clone()),
+            parent: node.parent,
+            children: node.children.clone(),
+        }
+    }
+}
+
+impl Document {
+    /// Take an owned snapshot of the structure of the document
+    pub fn snapshot(&self) -> DocumentSnapshot {
+        let root = self.root_node();
+        let nodes = std::iter::once(root)
+            .chain(root.descendants())
+            .map(|node| (node.id, NodeSnapshot::from_node(node)))
+            .collect();
+
+        DocumentSnapshot {
+            root: root.id,
+            nodes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::tests::{element, element_with_attrs, test_document, text};
+
+    #[test]
+    fn snapshot_captures_structure_and_sorts_attributes() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let div = element_with_attrs(&mut doc, html, "div", &[("id", "a"), ("class", "b")]);
+        let hello = text(&mut doc, div, "hello");
+        // Detached nodes are not part of the snapshot
+        let detached = doc.create_text_node("detached");
+
+        let snapshot = doc.snapshot();
+        assert_eq!(snapshot.root, 0);
+        assert_eq!(snapshot.nodes.len(), 4);
+        assert!(snapshot.get(detached).is_none());
+
+        let div_snapshot = snapshot.get(div).unwrap();
+        assert_eq!(div_snapshot.kind, NodeKind::Element);
+        assert_eq!(div_snapshot.tag.as_deref(), Some("div"));
+        assert_eq!(div_snapshot.parent, Some(html));
+        assert_eq!(div_snapshot.children, vec![hello]);
+        let attr_names: Vec<&str> = div_snapshot.attrs.iter().map(|a| &*a.name).collect();
+        assert_eq!(attr_names, vec!["class", "id"]);
+
+        assert_eq!(snapshot.get(hello).unwrap().text.as_deref(), Some("hello"));
+
+        // Snapshots are unaffected by later mutations
+        doc.remove_and_drop_node(div);
+        assert_eq!(snapshot.get(div).unwrap().children, vec![hello]);
+    }
+
+    #[test]
+    fn snapshot_traversals_match_the_document() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let body = element(&mut doc, html, "body");
+        let a = text(&mut doc, body, "a");
+        let b = element(&mut doc, body, "div");
+
+        let snapshot = doc.snapshot();
+        let ids = |nodes: Vec<&NodeSnapshot>| nodes.iter().map(|node| node.id).collect::<Vec<_>>();
+        assert_eq!(snapshot.root_node().id, 0);
+        assert_eq!(snapshot.parent(a).map(|node| node.id), Some(body));
+        assert_eq!(ids(snapshot.children(body).collect()), vec![a, b]);
+        assert_eq!(ids(snapshot.ancestors(a).collect()), vec![body, html]);
+        assert_eq!(ids(snapshot.descendants(html).collect()), vec![body, a, b]);
+        assert_eq!(snapshot.children(usize::MAX).count(), 0);
+
+        // Snapshots can be moved to another thread
+        let handle = std::thread::spawn(move || snapshot.nodes.len());
+        assert_eq!(handle.join().unwrap(), 5);
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed_nodes() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let div = element_with_attrs(&mut doc, html, "div", &[("id", "a"), ("title", "t")]);
+        let label = text(&mut doc, div, "old");
+        let gone = text(&mut doc, html, "gone");
+        let old = doc.snapshot();
+        assert!(old.diff(&old).is_empty());
+
+        let span = element(&mut doc, html, "span");
+        doc.remove_and_drop_node(gone);
+        let text_data = doc.nodes[label].raw_dom_data.downcast_text_mut().unwrap();
+        text_data.content = "new".to_string();
+        let element = doc.nodes[div].element_data_mut().unwrap();
+        element.attrs.retain(|attr| &*attr.name.local != "title");
+        element.attrs[0].value = "b".to_string();
+
+        let attr_diff = |name: &str, old: Option<&str>, new: Option<&str>| AttributeDiff {
+            name: name.to_string(),
+            namespace: String::new(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        };
+        let changed = |id, attrs, text_changed, children_changed| NodeDiff::Changed {
+            id,
+            attrs,
+            text_changed,
+            children_changed,
+        };
+        assert_eq!(
+            old.diff(&doc.snapshot()),
+            vec![
+                changed(html, Vec::new(), false, true),
+                changed(
+                    div,
+                    vec![
+                        attr_diff("id", Some("a"), Some("b")),
+                        attr_diff("title", Some("t"), None)
+                    ],
+                    false,
+                    false
+                ),
+                changed(label, Vec::new(), true, false),
+                NodeDiff::Removed { id: gone },
+                NodeDiff::Added { id: span },
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn snapshot_serializes_to_deterministic_json() {
+        let mut doc = test_document();
+        let html = element(&mut doc, 0, "html");
+        let p = element_with_attrs(&mut doc, html, "p", &[("title", "t"), ("id", "a")]);
+        text(&mut doc, p, "hi");
+
+        let expected = concat!(
+            r#"{"root":0,"nodes":{"#,
+            r#""0":{"id":0,"kind":"Document","tag":null,"namespace":null,"attrs":[],"#,
+            r#""text":null,"parent":null,"children":[1]},"#,
+            r#""1":{"id":1,"kind":"Element","tag":"html","#,
+            r#""namespace":"http://www.w3.org/1999/xhtml","attrs":[],"#,
+            r#""text":null,"parent":0,"children":[2]},"#,
+            r#""2":{"id":2,"kind":"Element","tag":"p","#,
+            r#""namespace":"http://www.w3.org/1999/xhtml","#,
+            r#""attrs":[{"name":"id","namespace":"","value":"a"},"#,
+            r#"{"name":"title","namespace":"","value":"t"}],"#,
+            r#""text":null,"parent":1,"children":[3]},"#,
+            r#""3":{"id":3,"kind":"Text","tag":null,"namespace":null,"attrs":[],"#,
+            r#""text":"hi","parent":2,"children":[]}"#,
+            r#"}}"#,
+        );
+        assert_eq!(serde_json::to_string(&doc.snapshot()).unwrap(), expected);
+    }
+}
@@ -1054,12 +1054,6 @@ impl<'a> Iterator for Traverser<'a> {
     }
 }
 
-impl std::hash::Hash for BlitzNode<'_> {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        state.write_usize(self.id)
-    }
-}
-
 /// Handle custom painters like images for layouting
 ///
 /// todo: actually implement this
@@ -6,7 +6,7 @@ use std::borrow::Cow;
 use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::collections::HashSet;
 
-use blitz_dom::node::{Attribute, ElementNodeData, Node, NodeData};
+use blitz_dom::node::{Attribute, Node, NodeData};
 use blitz_dom::Document;
 use blitz_traits::net::SharedProvider;
 use html5ever::{
@@ -226,22 +226,7 @@ impl<'b> TreeSink for DocumentHtmlParser<'b> {
         _flags: ElementFlags,
     ) -> Self::Handle {
         let attrs = attrs.into_iter().map(html5ever_to_blitz_attr).collect();
-        let mut data = ElementNodeData::new(name.clone(), attrs);
-        data.flush_style_attribute(&self.doc.borrow().guard);
-
-        let id = self.create_node(NodeData::Element(data));
-        let node = self.node(id);
-
-        // Initialise style data
-        *node.stylo_element_data.borrow_mut() = Some(Default::default());
-
-        let id_attr = node.attr(local_name!("id")).map(|id| id.to_string());
-        drop(node);
-
-        // If the node has an "id" attribute, store it in the ID map.
-        if let Some(id_attr) = id_attr {
-            self.doc.borrow_mut().nodes_to_id.insert(id_attr, id);
-        }
+        let id = self.doc.borrow_mut().create_element(name.clone(), attrs);
 
         // Custom post-processing by element tag name
         match name.local.as_ref() {
@@ -274,6 +274,17 @@ impl WriteMutations for MutationWriter<'_> {
 
         self.doc.snapshot_node(node_id);
 
+        // Keep the id map and text input state in sync with the attribute
+        let local_name = qual_name(name, ns).local;
+        match value {
+            AttributeValue::Text(val) => {
+                self.doc
+                    .sync_attribute_state(node_id, &local_name, Some(val.as_str()))
+            }
+            AttributeValue::None => self.doc.sync_attribute_state(node_id, &local_name, None),
+            _ => {}
+        }
+
         let node = &mut self.doc.nodes[node_id];
 
         let stylo_element_data = &mut *node.stylo_element_data.borrow_mut();
@@ -287,13 +298,6 @@ impl WriteMutations for MutationWriter<'_> {
             }
             // FIXME: support other non-text attributes
             else if let AttributeValue::Text(val) = value {
-                if name == "value" {
-                    // Update text input value
-                    if let Some(input_data) = element.text_input_data_mut() {
-                        input_data.set_text(&mut self.doc.font_ctx, &mut self.doc.layout_ctx, val);
-                    }
-                }
-
                 // FIXME check namespace
                 let existing_attr = element
                     .attrs
@@ -319,13 +323,6 @@ impl WriteMutations for MutationWriter<'_> {
             }
 
             if let AttributeValue::None = value {
-                // Update text input value
-                if name == "value" {
-                    if let Some(input_data) = element.text_input_data_mut() {
-                        input_data.set_text(&mut self.doc.font_ctx, &mut self.doc.layout_ctx, "");
-                    }
-                }
-
                 // FIXME: check namespace
                 element.attrs.retain(|attr| attr.name.local != *name);
             }
@@ -354,33 +351,27 @@ impl WriteMutations for MutationWriter<'_> {
         }
     }
 
-    fn create_event_listener(&mut self, _name: &'static str, _id: ElementId) {
+    fn create_event_listener(&mut self, name: &'static str, id: ElementId) {
         // we're going to actually set the listener here as a placeholder - in JS this would also be a placeholder
         // we might actually just want to attach the attribute to the root element (delegation)
-        self.set_attribute(
-            _name,
-            None,
-            &AttributeValue::Text("<rust func>".into()),
-            _id,
-        );
+        self.set_attribute(name, None, &AttributeValue::Text("<rust func>".into()), id);
 
         // also set the data-dioxus-id attribute so we can find the element later
         self.set_attribute(
             "data-dioxus-id",
             None,
-            &AttributeValue::Text(_id.0.to_string()),
-            _id,
+            &AttributeValue::Text(id.0.to_string()),
+            id,
         );
 
-        // let node_id = self.state.element_to_node_id(id);
-        // let mut node = self.rdom.get_mut(node_id).unwrap();
-        // node.add_event_listener(name);
+        // record the listener on the node so that the listener queries on Document can find it
+        let node_id = self.state.element_to_node_id(id);
+        self.doc.nodes[node_id].add_event_listener(name);
     }
 
-    fn remove_event_listener(&mut self, _name: &'static str, _id: ElementId) {
-        // let node_id = self.state.element_to_node_id(id);
-        // let mut node = self.rdom.get_mut(node_id).unwrap();
-        // node.remove_event_listener(name);
+    fn remove_event_listener(&mut self, name: &'static str, id: ElementId) {
+        let node_id = self.state.element_to_node_id(id);
+        self.doc.nodes[node_id].remove_event_listener(name);
     }
 
     fn remove_node(&mut self, id: ElementId) {